@@ -0,0 +1,161 @@
+use std::{borrow::Cow, cell::RefCell, collections::HashMap, rc::Rc};
+
+use rustyline::{
+    completion::{Completer, Pair},
+    highlight::Highlighter,
+    hint::Hinter,
+    validate::Validator,
+    Context, Helper,
+};
+
+/// The table and column names of one session, offered by tab completion
+/// while it is the active session.
+#[derive(Default)]
+pub struct CompletionSchema {
+    pub tables: Vec<String>,
+    pub columns: HashMap<String, Vec<String>>,
+}
+
+/// Tab completion for the prompt. Completes command verbs, table names
+/// after verbs that take a table and after `FROM`/`JOIN`-like keywords,
+/// and column names elsewhere in SQL.
+#[derive(Default)]
+pub struct Completion {
+    verbs: Vec<String>,
+    table_verbs: Vec<String>,
+    /// Shared with the active session, which reloads it.
+    schema: Rc<RefCell<CompletionSchema>>,
+    /// ANSI colour code the prompt is shown in.
+    prompt_color: Option<&'static str>,
+}
+
+impl Completion {
+    const TABLE_KEYWORDS: &[&str] = &["from", "join", "update", "into", "table"];
+    const COLUMN_KEYWORDS: &[&str] = &[
+        "select", "where", "and", "or", "by", "on", "set", "having", "not",
+    ];
+
+    /// `table_verbs` are the verbs whose first argument is a table.
+    pub fn set_verbs(&mut self, verbs: &[&str], table_verbs: &[&str]) {
+        self.verbs = verbs.iter().map(|a| a.to_string()).collect();
+        self.table_verbs = table_verbs.iter().map(|a| a.to_string()).collect();
+    }
+
+    pub fn set_schema(&mut self, schema: Rc<RefCell<CompletionSchema>>) {
+        self.schema = schema;
+    }
+
+    pub fn set_prompt_color(&mut self, color: Option<&'static str>) {
+        self.prompt_color = color;
+    }
+
+    fn candidates(&self, line: &str, start: usize, word: &str) -> Vec<String> {
+        let before = &line[..start];
+        let previous = before
+            .split(|c: char| c.is_whitespace() || c == '(')
+            .rev()
+            .find(|a| !a.is_empty());
+        let previous = match previous {
+            Some(previous) => previous.to_lowercase(),
+            None => return self.verbs.clone(),
+        };
+        let first = before
+            .split_whitespace()
+            .next()
+            .unwrap_or_default()
+            .to_lowercase();
+
+        let schema = self.schema.borrow();
+        if let Some((table, _)) = word.split_once('.') {
+            // `table.column`
+            return schema
+                .columns
+                .get(table)
+                .map(|c| c.iter().map(|a| format!("{}.{}", table, a)).collect())
+                .unwrap_or_default();
+        }
+        let is_table_argument =
+            self.table_verbs.contains(&first) && before.split_whitespace().count() == 1;
+        if is_table_argument || Completion::TABLE_KEYWORDS.contains(&previous.as_str()) {
+            return schema.tables.clone();
+        }
+        if Completion::COLUMN_KEYWORDS.contains(&previous.as_str()) || previous.ends_with(',') {
+            return self.statement_columns(line);
+        }
+        Vec::new()
+    }
+
+    /// Columns of the tables named in `line`, or of every table if none
+    /// are named yet.
+    fn statement_columns(&self, line: &str) -> Vec<String> {
+        let words = line
+            .split(|c: char| c.is_whitespace() || c == ',' || c == '(' || c == ')')
+            .filter(|a| !a.is_empty())
+            .collect::<Vec<&str>>();
+        let mut named = Vec::new();
+        for pair in words.windows(2) {
+            if Completion::TABLE_KEYWORDS.contains(&pair[0].to_lowercase().as_str()) {
+                named.push(pair[1].trim_end_matches(';'));
+            }
+        }
+        let mut columns = self
+            .schema
+            .borrow()
+            .columns
+            .iter()
+            .filter(|(table, _)| named.is_empty() || named.contains(&table.as_str()))
+            .flat_map(|(_, c)| c.iter().cloned())
+            .collect::<Vec<String>>();
+        columns.sort();
+        columns.dedup();
+        columns
+    }
+}
+
+impl Completer for Completion {
+    type Candidate = Pair;
+
+    fn complete(
+        &self,
+        line: &str,
+        pos: usize,
+        _: &Context<'_>,
+    ) -> rustyline::Result<(usize, Vec<Pair>)> {
+        let start = line[..pos]
+            .rfind(|c: char| c.is_whitespace() || c == ',' || c == '(')
+            .map(|i| i + 1)
+            .unwrap_or(0);
+        let word = &line[start..pos];
+        let lower = word.to_lowercase();
+        // After `@session`, complete as if the command stood alone.
+        let offset = match line.strip_prefix('@').and_then(|a| a.find(' ')) {
+            Some(i) if i + 1 < start => i + 2,
+            _ => 0,
+        };
+        let mut matches = self
+            .candidates(&line[offset..], start - offset, word)
+            .into_iter()
+            .filter(|a| a.to_lowercase().starts_with(&lower))
+            .map(|a| Pair {
+                display: a.clone(),
+                replacement: a,
+            })
+            .collect::<Vec<Pair>>();
+        matches.sort_by(|a, b| a.display.cmp(&b.display));
+        Ok((start, matches))
+    }
+}
+
+impl Hinter for Completion {
+    type Hint = String;
+}
+impl Highlighter for Completion {
+    fn highlight_prompt<'b, 's: 'b, 'p: 'b>(&'s self, prompt: &'p str, _: bool) -> Cow<'b, str> {
+        match self.prompt_color {
+            Some(color) => Cow::Owned(format!("\x1B[{}m{}\x1B[0m", color, prompt)),
+            None => Cow::Borrowed(prompt),
+        }
+    }
+}
+impl Validator for Completion {}
+impl Helper for Completion {}
@@ -1,62 +1,198 @@
-use crate::pg::PostgresCredentials;
+use super::{
+    Completion, CompletionSchema, ExpandedMode, PagedOutput, StatementBuffer, TableOutput,
+};
+use crate::pg::{PgTermError, PostgresCredentials};
+use rustyline::{
+    error::ReadlineError,
+    history::{DefaultHistory, History},
+    Config, Editor,
+};
 use std::{
-    collections::HashMap,
+    cell::RefCell,
+    collections::{HashMap, VecDeque},
     env,
-    io::{stdin, stdout, Stdin, Stdout, Write},
+    io::{stdin, stdout, IsTerminal, Stdout, Write},
+    path::PathBuf,
+    rc::Rc,
 };
 
 pub struct IOControl {
     startup_parameters: HashMap<String, String>,
-    cin: Stdin,
+    editor: Editor<Completion, DefaultHistory>,
+    /// Where history is kept between sessions, if a home directory is known.
+    history_path: Option<PathBuf>,
     cout: Stdout,
+    null_marker: String,
+    expanded: ExpandedMode,
+    /// Whether long output goes through the pager.
+    pager: bool,
+    /// Shown before the prompt, such as the active session.
+    prompt_name: String,
+    statement: StatementBuffer,
+    /// Statements completed by the last line that have not been returned
+    /// by `read_command` yet.
+    queued: VecDeque<String>,
 }
 impl IOControl {
+    const DEFAULT_NULL_MARKER: &str = "<null>";
+    const HISTORY_SIZE: usize = 1000;
+    /// Colours the prompt can be shown in, with their ANSI codes.
+    const PROMPT_COLORS: &[(&str, &str)] = &[
+        ("red", "31"),
+        ("green", "32"),
+        ("yellow", "33"),
+        ("blue", "34"),
+        ("magenta", "35"),
+        ("cyan", "36"),
+        ("white", "37"),
+    ];
+
     pub fn get_startup_parameters(&self) -> &HashMap<String, String> {
-        return &self.startup_parameters;
+        &self.startup_parameters
+    }
+
+    /// Wipes the password given at startup, once the credentials have
+    /// been made.
+    pub fn forget_startup_password(&mut self) {
+        PostgresCredentials::wipe_params(&mut self.startup_parameters);
     }
 
     pub fn create() -> IOControl {
         let mut parsed_args: HashMap<String, String> = HashMap::new();
-        let input_args = env::args().collect::<Vec<String>>()[1..].to_vec();
+        let mut input_args = env::args().skip(1);
+        while let Some(arg) = input_args.next() {
+            match arg.strip_prefix('-') {
+                Some(key) => {
+                    if let Some(value) = input_args.next() {
+                        parsed_args.insert(key.to_string(), value);
+                    }
+                }
+                // A URI or `key=value` connection string.
+                None => {
+                    parsed_args.insert(PostgresCredentials::CONNINFO_KEY.to_string(), arg);
+                }
+            }
+        }
 
-        let input_count = input_args.len();
-        let mut current_input_i = 1;
-        while current_input_i < input_count {
-            parsed_args.insert(
-                input_args[current_input_i - 1].clone()[1..].to_string(),
-                input_args[current_input_i].clone(),
-            );
-            current_input_i += 2;
+        let config = Config::builder()
+            .max_history_size(IOControl::HISTORY_SIZE)
+            .and_then(|c| c.history_ignore_dups(true))
+            .map(|c| c.build())
+            .unwrap_or_default();
+        let mut editor = Editor::with_config(config).expect("Could not initialise line editing.");
+        editor.set_helper(Some(Completion::default()));
+        let history_path = env::var_os("HOME").map(|h| PathBuf::from(h).join(".pgterm_history"));
+        if let Some(path) = &history_path {
+            _ = editor.load_history(path);
         }
 
         IOControl {
             startup_parameters: parsed_args,
-            cin: stdin(),
+            editor,
+            history_path,
             cout: stdout(),
+            null_marker: IOControl::DEFAULT_NULL_MARKER.to_string(),
+            expanded: ExpandedMode::Auto,
+            pager: true,
+            prompt_name: String::new(),
+            statement: StatementBuffer::new(),
+            queued: VecDeque::new(),
+        }
+    }
+
+    /// Sets the verbs offered by tab completion; `table_verbs` take a
+    /// table name as their first argument.
+    pub fn set_completion_verbs(&mut self, verbs: &[&str], table_verbs: &[&str]) {
+        if let Some(helper) = self.editor.helper_mut() {
+            helper.set_verbs(verbs, table_verbs);
         }
     }
 
+    /// Completes the table and column names of `schema`, the cache of the
+    /// active session.
+    pub fn set_completion_schema(&mut self, schema: Rc<RefCell<CompletionSchema>>) {
+        if let Some(helper) = self.editor.helper_mut() {
+            helper.set_schema(schema);
+        }
+    }
+
+    pub fn set_prompt_name(&mut self, name: &str) {
+        self.prompt_name = name.to_string();
+    }
+
+    /// Colours the prompt, or restores the default with `None`. Fails
+    /// for a colour that is not known.
+    pub fn set_prompt_color(&mut self, color: Option<&str>) -> Result<(), PgTermError> {
+        let code = match color {
+            Some(color) => Some(
+                IOControl::PROMPT_COLORS
+                    .iter()
+                    .find(|(name, _)| *name == color)
+                    .map(|(_, code)| *code)
+                    .ok_or_else(|| {
+                        PgTermError::Parse(format!(
+                            "{:?} is not a colour, use one of {}",
+                            color,
+                            IOControl::PROMPT_COLORS
+                                .iter()
+                                .map(|(name, _)| *name)
+                                .collect::<Vec<&str>>()
+                                .join(", ")
+                        ))
+                    })?,
+            ),
+            None => None,
+        };
+        if let Some(helper) = self.editor.helper_mut() {
+            helper.set_prompt_color(code);
+        }
+        Ok(())
+    }
+
     pub fn complete_credentials(&mut self, credentials: &mut PostgresCredentials) {
-        if credentials.host == "" {
+        if credentials.host.is_empty() {
             if let Some(r) = self.ask_for("Host:") {
                 credentials.host = r;
             }
         }
-        if credentials.username == "" {
+        if credentials.username.is_empty() {
             if let Some(r) = self.ask_for("Username:") {
                 credentials.username = r;
             }
         }
-        if credentials.password == "" && credentials.pass_required {
-            if let Some(r) = self.ask_for("Password:") {
-                credentials.password = r;
-            }
-        }
-        if credentials.database == "" {
+        if credentials.database.is_empty() {
             if let Some(r) = self.ask_for("Database:") {
                 credentials.database = r;
             }
         }
+        if credentials.password.is_empty() {
+            match credentials.password_from_pgpass() {
+                Ok(Some(password)) => credentials.password = password,
+                Ok(None) => {}
+                Err(er) => self.error(&er),
+            }
+        }
+        if credentials.password.is_empty() && credentials.pass_required {
+            if let Some(r) = self.ask_for_password("Password:") {
+                credentials.password = r;
+            }
+        }
+    }
+
+    /// Whether input comes from someone at a terminal, who can be asked
+    /// again when something goes wrong.
+    pub fn is_interactive(&self) -> bool {
+        stdin().is_terminal()
+    }
+
+    /// Reads a password without echoing it when at a terminal, so it is
+    /// neither shown nor kept in the scrollback.
+    pub fn ask_for_password(&mut self, title: &str) -> Option<String> {
+        if !self.is_interactive() {
+            return self.ask_for(title);
+        }
+        _ = self.cout.flush();
+        rpassword::prompt_password(format!("{} ", title)).ok()
     }
 
     pub fn clear(&mut self) {
@@ -64,52 +200,174 @@ impl IOControl {
     }
 
     pub fn ask_for(&mut self, title: &str) -> Option<String> {
-        let mut handle = self.cout.lock();
-        handle.write_all(title.as_bytes()).ok()?;
-        handle.write_all(" ".as_bytes()).ok()?;
-        handle.flush().ok()?;
-        let mut read = String::new();
-        self.cin.read_line(&mut read).ok()?;
-        return Some(read.trim().to_string());
+        self.read_line(title).ok()
     }
 
-    pub fn create_table(&mut self, header: &[String], values: Vec<Vec<String>>, col_size: usize) {
-        let mut lines: Vec<String> = Vec::new();
-        let mut header_line = "| ".to_string();
-        for v in header {
-            header_line += &self.pad_value(v.clone(), col_size.clone());
-            header_line += " | ";
-        }
-        lines.push(header_line[0..header_line.chars().count() - 1].to_string());
-        for l in values {
-            let mut line = "| ".to_string();
-            for v in l {
-                let input_val = v.to_owned();
-                line += &self.pad_value(input_val, col_size.clone());
-                line += " | ";
+    fn read_line(&mut self, title: &str) -> Result<String, ReadlineError> {
+        _ = self.cout.flush();
+        let read = self.editor.readline(&format!("{} ", title))?;
+        Ok(read.trim().to_string())
+    }
+
+    /// Reads the next command or SQL statement. A line for which
+    /// `is_command` is true is returned as is; anything else is SQL and is
+    /// collected with a `...>` prompt until it is terminated by `;`.
+    /// Entering `\r` or pressing Ctrl-C discards a partially typed
+    /// statement, and `!N` re-runs entry `N` of `history_lines`. Commands
+    /// for which `is_private` is true are not added to the history.
+    /// Returns `None` at the end of input.
+    pub fn read_command(
+        &mut self,
+        is_command: impl Fn(&str) -> bool,
+        is_private: impl Fn(&str) -> bool,
+    ) -> Option<String> {
+        loop {
+            if let Some(statement) = self.queued.pop_front() {
+                return Some(statement);
+            }
+            let prompt = if self.statement.is_empty() {
+                format!("{}>", self.prompt_name)
+            } else {
+                format!("{}...>", self.prompt_name)
+            };
+            let mut line = match self.read_line(&prompt) {
+                Ok(line) => line,
+                Err(ReadlineError::Interrupted) => {
+                    self.statement.clear();
+                    continue;
+                }
+                Err(_) => return None,
+            };
+            if let Some(event) = line.strip_prefix('!').filter(|a| !a.is_empty()) {
+                match self.history_entry(event) {
+                    Some(entry) => {
+                        self.publish(&entry);
+                        line = entry;
+                    }
+                    None => {
+                        self.publish(&format!("!{}: event not found", event));
+                        continue;
+                    }
+                }
+            }
+            if line == "\\r" {
+                self.statement.clear();
+                self.publish("Query buffer reset (cleared).");
+                continue;
+            }
+            if self.statement.is_empty() && (line.is_empty() || is_command(&line)) {
+                if !is_private(&line) {
+                    self.remember(&line);
+                }
+                return Some(line);
+            }
+            let completed = self.statement.push_line(&line);
+            for statement in &completed {
+                self.remember(statement);
             }
-            lines.push(line[0..line.chars().count() - 1].to_string());
+            self.queued.extend(completed);
         }
-        self.publish_lines(&lines);
+    }
+
+    /// Adds a command or complete statement to the history and saves it.
+    fn remember(&mut self, entry: &str) {
+        if entry.is_empty() {
+            return;
+        }
+        _ = self.editor.add_history_entry(entry);
+        if let Some(path) = &self.history_path {
+            _ = self.editor.append_history(path);
+        }
+    }
+
+    /// Looks up `!N` (1-based, as listed by `history_lines`) or `!!`.
+    fn history_entry(&self, event: &str) -> Option<String> {
+        let history = self.editor.history();
+        let index = if event == "!" {
+            history.len().checked_sub(1)?
+        } else {
+            event.parse::<usize>().ok()?.checked_sub(1)?
+        };
+        history.iter().nth(index).cloned()
+    }
+
+    /// Every remembered command and statement, numbered for `!N`.
+    pub fn history_lines(&self) -> Vec<String> {
+        self.editor
+            .history()
+            .iter()
+            .enumerate()
+            .map(|(i, entry)| format!("{:>5}  {}", i + 1, entry))
+            .collect()
+    }
+
+    /// The text shown in place of SQL NULL by `create_table`.
+    pub fn null_marker(&self) -> &str {
+        &self.null_marker
+    }
+
+    pub fn set_null_marker(&mut self, marker: &str) {
+        self.null_marker = marker.to_string();
+    }
+
+    pub fn expanded(&self) -> ExpandedMode {
+        self.expanded
+    }
+
+    pub fn set_expanded(&mut self, mode: ExpandedMode) {
+        self.expanded = mode;
+    }
+
+    /// Prints rows as a bordered table, or as one block per record in
+    /// expanded mode. See `TableOutput` for writing rows in batches.
+    pub fn create_table(
+        &mut self,
+        header: &[String],
+        values: Vec<Vec<Option<String>>>,
+        numeric: &[bool],
+    ) {
+        let mut output = TableOutput::new(self, header);
+        output.write_rows(values, numeric);
+        output.finish();
     }
 
     pub fn publish_lines(&mut self, lines: &[String]) {
         let mut handle = self.cout.lock();
         for l in lines {
             _ = handle.write_all(l.as_bytes());
-            _ = handle.write(&[b'\n']);
+            _ = handle.write(b"\n");
         }
     }
+    /// Like `publish_lines`, but output taller than the terminal is shown
+    /// through the pager.
+    pub fn page_lines(&mut self, lines: &[String]) {
+        let mut output = PagedOutput::new(self);
+        for l in lines {
+            if !output.write_line(l.clone()) {
+                break;
+            }
+        }
+        output.finish();
+    }
+
+    pub fn pager(&self) -> bool {
+        self.pager
+    }
+
+    pub fn set_pager(&mut self, pager: bool) {
+        self.pager = pager;
+    }
+
     pub fn publish(&mut self, l: &str) {
         let mut handle = self.cout.lock();
         _ = handle.write_all(l.as_bytes());
-        _ = handle.write(&[b'\n']);
+        _ = handle.write(b"\n");
     }
 
     pub fn announce(&mut self, titles: &[&str]) {
         self.clear();
         if let Some((w, h)) = term_size::dimensions() {
-            let top_pad = h / 2 - titles.len() - 2;
+            let top_pad = (h / 2).saturating_sub(titles.len() + 2);
             let bot_pad = h / 2;
 
             let mut top_pad_string = String::new();
@@ -118,22 +376,22 @@ impl IOControl {
             let mut t_i = 0;
             let mut b_i = 0;
             while t_i < top_pad {
-                top_pad_string.push_str(&"\n");
+                top_pad_string.push('\n');
                 t_i += 1;
             }
             while b_i < bot_pad {
-                bot_pad_string.push_str(&"\n");
+                bot_pad_string.push('\n');
                 b_i += 1;
             }
 
             let mut handle = self.cout.lock();
             _ = handle.write_all(top_pad_string.as_bytes());
             for t in titles {
-                let left_pad = (w - t.len()) / 2;
+                let left_pad = w.saturating_sub(t.len()) / 2;
                 let mut l_i = 0;
                 let mut left_pad_string = String::new();
                 while l_i < left_pad {
-                    left_pad_string.push_str(&" ");
+                    left_pad_string.push(' ');
                     l_i += 1;
                 }
                 _ = handle.write_all(left_pad_string.as_bytes());
@@ -142,6 +400,11 @@ impl IOControl {
             }
             _ = handle.write_all(bot_pad_string.as_bytes());
             _ = handle.flush();
+        } else {
+            // Not a terminal, so there is nothing to centre against.
+            for t in titles {
+                self.publish(t);
+            }
         }
     }
 
@@ -162,35 +425,43 @@ impl IOControl {
         output += command;
         output += ": ";
         output += usage;
-        self.publish_lines(&[
-            "Invalid usage!".to_string(),
-            output
-        ]);
-    }
-
-    fn pad_value(&self, mut input: String, mut fixed_size: usize) -> String {
-    	let mut line = String::new();
-        let mut val_size = input.chars().count();
-        if val_size > fixed_size {
-            input = input[0..fixed_size - 5].to_string();
-            input += "(...)";
-            val_size = fixed_size;
-        }
-        let pad_len = fixed_size - val_size;
-        let mut pad = String::new();
-        let mut pad_i = 0;
-        while pad_i < pad_len / 2 {
-            pad += " ";
-            pad_i += 1;
-        }
-        line += &pad;
-        // Add extra space when number is odd
-        // due to integer divison
-        if (pad_len / 2) * 2 < pad_len {
-            line += " ";
-        }
-        line += &input;
-        line += &pad;
-        line
+        self.publish_lines(&["Invalid usage!".to_string(), output]);
+    }
+
+    /// Prints an error the way psql does: the server's message, any detail
+    /// and hint, and a caret under the failing position of the query.
+    pub fn error(&mut self, er: &PgTermError) {
+        let mut lines: Vec<String> = Vec::new();
+        if let PgTermError::Sql(sql) = er {
+            lines.push(format!("ERROR {}:  {}", sql.code, sql.message));
+            if let Some(detail) = &sql.detail {
+                lines.push(format!("DETAIL:  {}", detail));
+            }
+            if let Some(hint) = &sql.hint {
+                lines.push(format!("HINT:  {}", hint));
+            }
+            if let (Some(position), Some(query)) = (sql.position, &sql.query) {
+                lines.extend(self.caret_lines(query, position as usize));
+            }
+        } else {
+            lines.push(er.to_string());
+        }
+        self.publish_lines(&lines);
+    }
+
+    /// The query line containing the 1-based character `position`, with a
+    /// caret beneath that character.
+    fn caret_lines(&self, query: &str, position: usize) -> Vec<String> {
+        let mut remaining = position.saturating_sub(1);
+        for (number, line) in query.split('\n').enumerate() {
+            let length = line.chars().count();
+            if remaining <= length {
+                let prefix = format!("LINE {}: ", number + 1);
+                let pad = " ".repeat(prefix.chars().count() + remaining);
+                return vec![format!("{}{}", prefix, line), format!("{}^", pad)];
+            }
+            remaining -= length + 1;
+        }
+        Vec::new()
     }
 }
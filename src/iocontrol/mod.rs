@@ -1,3 +1,10 @@
+pub mod completion;
+#[allow(clippy::module_inception)]
 pub mod iocontrol;
+pub mod output;
+pub mod statement;
 
+pub use completion::{Completion, CompletionSchema};
 pub use iocontrol::IOControl;
+pub use output::{ExpandedMode, PagedOutput, TableOutput};
+pub use statement::StatementBuffer;
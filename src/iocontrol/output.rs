@@ -0,0 +1,489 @@
+use super::IOControl;
+use std::{
+    env,
+    fmt::Display,
+    io::{stdout, IsTerminal, Write},
+    process::{Child, ChildStdin, Command, Stdio},
+};
+use unicode_width::UnicodeWidthStr;
+
+/// When results are shown one record at a time instead of as a grid.
+#[derive(Clone, Copy, PartialEq)]
+pub enum ExpandedMode {
+    Off,
+    On,
+    /// Only when the grid would be wider than the terminal.
+    Auto,
+}
+
+impl ExpandedMode {
+    pub fn parse(mode: &str) -> Option<ExpandedMode> {
+        match mode {
+            "on" => Some(ExpandedMode::On),
+            "off" => Some(ExpandedMode::Off),
+            "auto" => Some(ExpandedMode::Auto),
+            _ => None,
+        }
+    }
+}
+
+impl Display for ExpandedMode {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            ExpandedMode::Off => write!(f, "off"),
+            ExpandedMode::On => write!(f, "on"),
+            ExpandedMode::Auto => write!(f, "auto"),
+        }
+    }
+}
+
+/// Output written a line at a time. Lines are held back until there are
+/// more than fit on the terminal, then everything goes to `$PAGER` (or
+/// `less -S`). Shorter output is printed directly.
+pub struct PagedOutput<'a> {
+    console: &'a mut IOControl,
+    state: PagerState,
+}
+
+enum PagerState {
+    Holding {
+        lines: Vec<String>,
+        height: usize,
+    },
+    Direct,
+    Paging {
+        child: Child,
+        input: ChildStdin,
+    },
+    /// The pager was quit before all output was written.
+    Closed,
+}
+
+impl<'a> PagedOutput<'a> {
+    const DEFAULT_PAGER: &'static str = "less -S";
+
+    pub fn new(console: &'a mut IOControl) -> PagedOutput<'a> {
+        let height = term_size::dimensions().map(|(_, h)| h);
+        let state = match height {
+            Some(height) if console.pager() && stdout().is_terminal() => PagerState::Holding {
+                lines: Vec::new(),
+                height,
+            },
+            _ => PagerState::Direct,
+        };
+        PagedOutput { console, state }
+    }
+
+    /// Returns false once the pager has been quit, after which nothing
+    /// more will be shown.
+    pub fn write_line(&mut self, line: String) -> bool {
+        match &mut self.state {
+            PagerState::Holding { lines, height } => {
+                lines.push(line);
+                // Leave a line for the prompt.
+                if lines.len() >= *height {
+                    let lines = std::mem::take(lines);
+                    self.state = match PagedOutput::spawn_pager() {
+                        Some((child, input)) => PagerState::Paging { child, input },
+                        None => PagerState::Direct,
+                    };
+                    for l in lines {
+                        if !self.write_line(l) {
+                            return false;
+                        }
+                    }
+                }
+                true
+            }
+            PagerState::Direct => {
+                self.console.publish(&line);
+                true
+            }
+            PagerState::Paging { input, .. } => {
+                // The pager closes its input when the user quits early.
+                if writeln!(input, "{}", line).is_err() {
+                    self.state = PagerState::Closed;
+                    return false;
+                }
+                true
+            }
+            PagerState::Closed => false,
+        }
+    }
+
+    /// Prints anything held back, or waits for the pager to be quit.
+    pub fn finish(self) {
+        match self.state {
+            PagerState::Holding { lines, .. } => self.console.publish_lines(&lines),
+            PagerState::Paging { mut child, input } => {
+                drop(input);
+                _ = child.wait();
+            }
+            PagerState::Direct | PagerState::Closed => {}
+        }
+    }
+
+    fn spawn_pager() -> Option<(Child, ChildStdin)> {
+        let command = env::var("PAGER")
+            .ok()
+            .filter(|a| !a.trim().is_empty())
+            .unwrap_or_else(|| PagedOutput::DEFAULT_PAGER.to_string());
+        let mut words = command.split_whitespace();
+        let mut child = Command::new(words.next()?)
+            .args(words)
+            .stdin(Stdio::piped())
+            .spawn()
+            .ok()?;
+        let input = child.stdin.take()?;
+        Some((child, input))
+    }
+}
+
+/// A result table written in batches of rows. The layout is chosen from
+/// the first batch, so later rows that are wider are cut short with `…`
+/// in grid mode and wrapped in expanded mode. Every column is at least
+/// one character wide, so there is room for the `…`.
+pub struct TableOutput<'a> {
+    output: PagedOutput<'a>,
+    header: Vec<String>,
+    null_marker: String,
+    expanded: ExpandedMode,
+    width: Option<usize>,
+    layout: Option<Layout>,
+    rows: usize,
+}
+
+enum Layout {
+    Grid {
+        widths: Vec<usize>,
+        numeric: Vec<bool>,
+        border: String,
+    },
+    Records {
+        label_width: usize,
+        value_width: usize,
+    },
+}
+
+impl<'a> TableOutput<'a> {
+    pub fn new(console: &'a mut IOControl, header: &[String]) -> TableOutput<'a> {
+        let null_marker = console.null_marker().to_string();
+        let expanded = console.expanded();
+        let width = console.width();
+        TableOutput {
+            output: PagedOutput::new(console),
+            header: header.iter().map(|h| printable(h)).collect(),
+            null_marker,
+            expanded,
+            width,
+            layout: None,
+            rows: 0,
+        }
+    }
+
+    /// Writes a batch of rows. `None` cells are SQL NULL and are shown
+    /// using the null marker. Columns flagged in `numeric` are
+    /// right-aligned. Returns false once the pager has been quit.
+    pub fn write_rows(&mut self, values: Vec<Vec<Option<String>>>, numeric: &[bool]) -> bool {
+        let values = values
+            .into_iter()
+            .map(|l| {
+                l.into_iter()
+                    .map(|v| v.unwrap_or_else(|| self.null_marker.clone()))
+                    .collect::<Vec<String>>()
+            })
+            .collect::<Vec<Vec<String>>>();
+        if self.layout.is_none() && !self.start(&values, numeric) {
+            return false;
+        }
+        for l in values {
+            self.rows += 1;
+            let lines = match &self.layout {
+                Some(Layout::Grid {
+                    widths, numeric, ..
+                }) => vec![grid_line(&l, widths, &|i| {
+                    if numeric.get(i).copied().unwrap_or(false) {
+                        Align::Right
+                    } else {
+                        Align::Left
+                    }
+                })],
+                Some(Layout::Records {
+                    label_width,
+                    value_width,
+                }) => record_lines(&self.header, &l, self.rows, *label_width, *value_width),
+                None => Vec::new(),
+            };
+            for line in lines {
+                if !self.output.write_line(line) {
+                    return false;
+                }
+            }
+        }
+        true
+    }
+
+    /// Ends the table and returns how many rows were written.
+    pub fn finish(mut self) -> usize {
+        if self.layout.is_none() {
+            self.start(&[], &[]);
+        }
+        if let Some(Layout::Grid { border, .. }) = &self.layout {
+            let border = border.clone();
+            self.output.write_line(border);
+        }
+        self.output.finish();
+        self.rows
+    }
+
+    /// Picks the layout and writes the header.
+    fn start(&mut self, values: &[Vec<String>], numeric: &[bool]) -> bool {
+        let mut widths = self
+            .header
+            .iter()
+            .map(|h| h.width().max(1))
+            .collect::<Vec<usize>>();
+        for l in values {
+            for (i, v) in l.iter().enumerate() {
+                widths[i] = widths[i].max(printable(v).width());
+            }
+        }
+        // Each column takes its width plus " | ", and the line one more.
+        let line_width = widths.iter().sum::<usize>() + 3 * widths.len() + 1;
+        let expand = match self.expanded {
+            ExpandedMode::On => true,
+            ExpandedMode::Off => false,
+            ExpandedMode::Auto => self.width.is_some_and(|w| line_width > w),
+        };
+
+        if expand {
+            let label_width = self.header.iter().map(|h| h.width()).max().unwrap_or(0);
+            let mut value_width = values
+                .iter()
+                .flatten()
+                .flat_map(|v| v.split('\n'))
+                .map(|v| printable(v).width())
+                .max()
+                .unwrap_or(0)
+                .max(MIN_COLUMN_WIDTH);
+            if let Some(available) = self.width {
+                let available = available.saturating_sub(label_width + 3);
+                value_width = value_width.min(available.max(MIN_COLUMN_WIDTH));
+            }
+            self.layout = Some(Layout::Records {
+                label_width,
+                value_width,
+            });
+            return true;
+        }
+
+        // Columns shrink, widest first, when the table would not fit.
+        if let Some(available) = self.width {
+            let available = available.saturating_sub(3 * widths.len() + 1);
+            while widths.iter().sum::<usize>() > available {
+                match widths.iter_mut().filter(|w| **w > MIN_COLUMN_WIDTH).max() {
+                    Some(widest) => *widest -= 1,
+                    None => break,
+                }
+            }
+        }
+        let border = format!(
+            "+{}+",
+            widths
+                .iter()
+                .map(|w| "-".repeat(w + 2))
+                .collect::<Vec<String>>()
+                .join("+")
+        );
+        let header = grid_line(&self.header, &widths, &|_| Align::Center);
+        self.layout = Some(Layout::Grid {
+            widths,
+            numeric: numeric.to_vec(),
+            border: border.clone(),
+        });
+        self.output.write_line(border.clone())
+            && self.output.write_line(header)
+            && self.output.write_line(border)
+    }
+}
+
+fn grid_line(cells: &[String], widths: &[usize], align: &dyn Fn(usize) -> Align) -> String {
+    let cells = cells
+        .iter()
+        .enumerate()
+        .map(|(i, v)| pad_value(&printable(v), widths[i], align(i)))
+        .collect::<Vec<String>>();
+    format!("| {} |", cells.join(" | "))
+}
+
+/// A `-[ RECORD n ]-` block with one `column | value` line per field.
+/// Values keep their line breaks and are wrapped instead of cut short.
+fn record_lines(
+    header: &[String],
+    values: &[String],
+    record: usize,
+    label_width: usize,
+    value_width: usize,
+) -> Vec<String> {
+    let title = format!("-[ RECORD {} ]", record);
+    let rule = (label_width + value_width + 3).saturating_sub(title.width());
+    let mut lines = vec![format!("{}{}", title, "-".repeat(rule))];
+    for (i, v) in values.iter().enumerate() {
+        let wrapped = v
+            .split('\n')
+            .flat_map(|part| wrap(&printable(part), value_width))
+            .collect::<Vec<String>>();
+        for (j, part) in wrapped.iter().enumerate() {
+            let label = if j == 0 { header[i].as_str() } else { "" };
+            let label = pad_value(label, label_width, Align::Left);
+            lines.push(format!("{} | {}", label, part).trim_end().to_string());
+        }
+    }
+    lines
+}
+
+/// Columns are never shrunk below this many characters to fit the terminal.
+const MIN_COLUMN_WIDTH: usize = 5;
+
+enum Align {
+    Left,
+    Center,
+    Right,
+}
+
+/// Replaces characters that would break the table layout.
+fn printable(value: &str) -> String {
+    value
+        .chars()
+        .map(|c| match c {
+            '\n' => '↵',
+            '\t' => ' ',
+            c if c.is_control() => '?',
+            c => c,
+        })
+        .collect()
+}
+
+/// Splits `input` into pieces at most `width` terminal columns wide. A
+/// character wider than `width` gets a piece of its own.
+fn wrap(input: &str, width: usize) -> Vec<String> {
+    let mut lines = Vec::new();
+    let mut line = String::new();
+    for c in input.chars() {
+        line.push(c);
+        // Measured as a whole, since emoji sequences are narrower than
+        // their characters.
+        if line.width() > width && line.len() > c.len_utf8() {
+            line.pop();
+            lines.push(std::mem::take(&mut line));
+            line.push(c);
+        }
+    }
+    lines.push(line);
+    lines
+}
+
+/// Fits `input` into `width` terminal columns, cutting it short with `…`
+/// when it is too wide.
+fn pad_value(input: &str, width: usize, align: Align) -> String {
+    let mut value = input.to_string();
+    if value.width() > width {
+        value.clear();
+        // A column with no room shows nothing, not even the `…`.
+        if width > 0 {
+            for c in input.chars() {
+                value.push(c);
+                if value.width() + 1 > width {
+                    value.pop();
+                    break;
+                }
+            }
+            value.push('…');
+        }
+    }
+    let pad = width.saturating_sub(value.width());
+    match align {
+        Align::Left => format!("{}{}", value, " ".repeat(pad)),
+        Align::Right => format!("{}{}", " ".repeat(pad), value),
+        Align::Center => format!(
+            "{}{}{}",
+            " ".repeat(pad / 2),
+            value,
+            " ".repeat(pad - pad / 2)
+        ),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn pad_value_cuts_to_narrow_widths() {
+        let cut = (0..=5)
+            .map(|w| pad_value("hello", w, Align::Left))
+            .collect::<Vec<String>>();
+        assert_eq!(cut, ["", "…", "h…", "he…", "hel…", "hello"]);
+    }
+
+    #[test]
+    fn pad_value_aligns() {
+        assert_eq!(pad_value("ab", 5, Align::Left), "ab   ");
+        assert_eq!(pad_value("ab", 5, Align::Right), "   ab");
+        assert_eq!(pad_value("ab", 5, Align::Center), " ab  ");
+    }
+
+    #[test]
+    fn pad_value_measures_wide_characters() {
+        assert_eq!(pad_value("中文字", 6, Align::Left), "中文字");
+        assert_eq!(pad_value("中文字", 4, Align::Left), "中… ");
+        assert_eq!(pad_value("中文字", 3, Align::Left), "中…");
+        assert_eq!(pad_value("中文字", 2, Align::Left), "… ");
+        assert_eq!(pad_value("😀😀", 3, Align::Right), "😀…");
+        assert_eq!(pad_value("❤\u{fe0f}ok", 3, Align::Left), "❤\u{fe0f}…");
+        // A family is one emoji two columns wide.
+        let family = "👨\u{200d}👩\u{200d}👧";
+        assert_eq!(pad_value(family, 2, Align::Left), family);
+    }
+
+    #[test]
+    fn pad_value_always_fills_the_width() {
+        let inputs = [
+            "",
+            "hello",
+            "中文字",
+            "😀😀",
+            "❤\u{fe0f}ok",
+            "e\u{301}te",
+            "👨\u{200d}👩\u{200d}👧x",
+        ];
+        for input in inputs {
+            for width in 0..=6 {
+                for align in [Align::Left, Align::Center, Align::Right] {
+                    let padded = pad_value(input, width, align);
+                    assert_eq!(padded.width(), width, "{:?} in {}", input, width);
+                }
+            }
+        }
+    }
+
+    #[test]
+    fn printable_replaces_control_characters() {
+        assert_eq!(printable("a\tb\nc\u{7}\r"), "a b↵c??");
+        assert_eq!(
+            grid_line(&["x\ny".to_string()], &[3], &|_| Align::Left),
+            "| x↵y |"
+        );
+    }
+
+    #[test]
+    fn wrap_splits_by_terminal_width() {
+        assert_eq!(wrap("abcdef", 4), ["abcd", "ef"]);
+        assert_eq!(wrap("", 4), [""]);
+        assert_eq!(wrap("中文字", 4), ["中文", "字"]);
+        assert_eq!(wrap("中文", 1), ["中", "文"]);
+        assert_eq!(wrap("ab", 0), ["a", "b"]);
+        let family = "👨\u{200d}👩\u{200d}👧";
+        assert_eq!(wrap(&family.repeat(2), 3), [family, family]);
+    }
+}
@@ -0,0 +1,321 @@
+/// Collects SQL typed over several lines until a statement is terminated
+/// by a `;` that is not inside a string, quoted identifier, dollar-quoted
+/// body or comment.
+pub struct StatementBuffer {
+    pending: String,
+}
+
+struct ScanResult {
+    /// Byte offsets just past each terminating `;`.
+    ends: Vec<usize>,
+    /// Whether there is anything besides whitespace and comments.
+    has_content: bool,
+    /// Whether the text ends inside a string, identifier or comment.
+    open: bool,
+}
+
+enum ScanState {
+    Normal,
+    SingleQuote { escapes: bool },
+    DoubleQuote,
+    Dollar(String),
+    BlockComment(usize),
+    LineComment,
+}
+
+impl Default for StatementBuffer {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl StatementBuffer {
+    pub fn new() -> StatementBuffer {
+        StatementBuffer {
+            pending: String::new(),
+        }
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.pending.is_empty()
+    }
+
+    pub fn clear(&mut self) {
+        self.pending.clear();
+    }
+
+    /// Adds a line of input and returns every statement it completes,
+    /// each including its terminating `;`. Anything after the last
+    /// terminator stays buffered.
+    pub fn push_line(&mut self, line: &str) -> Vec<String> {
+        if !self.pending.is_empty() {
+            self.pending.push('\n');
+        }
+        self.pending += line;
+
+        let mut statements = Vec::new();
+        let mut start = 0;
+        for end in StatementBuffer::scan(&self.pending).ends {
+            let statement = self.pending[start..end].trim();
+            if StatementBuffer::scan(statement).has_content {
+                statements.push(statement.to_string());
+            }
+            start = end;
+        }
+        self.pending = self.pending[start..].to_string();
+        let rest = StatementBuffer::scan(&self.pending);
+        if !rest.has_content && !rest.open {
+            // Only whitespace or finished comments are left over.
+            self.pending.clear();
+        }
+        statements
+    }
+
+    fn scan(text: &str) -> ScanResult {
+        let chars = text.chars().collect::<Vec<char>>();
+        let offsets = text.char_indices().map(|(o, _)| o).collect::<Vec<usize>>();
+        let mut state = ScanState::Normal;
+        let mut result = ScanResult {
+            ends: Vec::new(),
+            has_content: false,
+            open: false,
+        };
+        let mut i = 0;
+        while i < chars.len() {
+            if let ScanState::Normal = state {
+                let c = chars[i];
+                if c == ';' {
+                    result.ends.push(offsets[i] + 1);
+                    i += 1;
+                    continue;
+                }
+                let starts_comment = (c == '-' && chars.get(i + 1) == Some(&'-'))
+                    || (c == '/' && chars.get(i + 1) == Some(&'*'));
+                if !c.is_whitespace() && !starts_comment {
+                    result.has_content = true;
+                }
+            }
+            i = StatementBuffer::step(&chars, i, &mut state);
+        }
+        result.open = !matches!(state, ScanState::Normal | ScanState::LineComment);
+        result
+    }
+
+    /// Consumes the token at `i` and returns the index of the next one.
+    fn step(chars: &[char], i: usize, state: &mut ScanState) -> usize {
+        let c = chars[i];
+        let next = chars.get(i + 1).copied();
+        match state {
+            ScanState::Normal => match c {
+                '\'' => {
+                    let escapes = i > 0
+                        && (chars[i - 1] == 'E' || chars[i - 1] == 'e')
+                        && (i < 2 || !is_identifier_char(chars[i - 2]));
+                    *state = ScanState::SingleQuote { escapes };
+                    i + 1
+                }
+                '"' => {
+                    *state = ScanState::DoubleQuote;
+                    i + 1
+                }
+                '-' if next == Some('-') => {
+                    *state = ScanState::LineComment;
+                    i + 2
+                }
+                '/' if next == Some('*') => {
+                    *state = ScanState::BlockComment(1);
+                    i + 2
+                }
+                '$' if i == 0 || !is_identifier_char(chars[i - 1]) => match dollar_tag(chars, i) {
+                    Some(tag) => {
+                        let len = tag.chars().count();
+                        *state = ScanState::Dollar(tag);
+                        i + len
+                    }
+                    None => i + 1,
+                },
+                _ => i + 1,
+            },
+            ScanState::SingleQuote { escapes } => {
+                // A backslash escape in E'' strings, or a doubled quote.
+                if (*escapes && c == '\\') || (c == '\'' && next == Some('\'')) {
+                    i + 2
+                } else {
+                    if c == '\'' {
+                        *state = ScanState::Normal;
+                    }
+                    i + 1
+                }
+            }
+            ScanState::DoubleQuote => {
+                if c == '"' && next == Some('"') {
+                    i + 2
+                } else {
+                    if c == '"' {
+                        *state = ScanState::Normal;
+                    }
+                    i + 1
+                }
+            }
+            ScanState::Dollar(tag) => {
+                let tag_chars = tag.chars().collect::<Vec<char>>();
+                if chars[i..].starts_with(&tag_chars) {
+                    let len = tag_chars.len();
+                    *state = ScanState::Normal;
+                    i + len
+                } else {
+                    i + 1
+                }
+            }
+            ScanState::BlockComment(depth) => {
+                if c == '/' && next == Some('*') {
+                    *depth += 1;
+                    i + 2
+                } else if c == '*' && next == Some('/') {
+                    *depth -= 1;
+                    if *depth == 0 {
+                        *state = ScanState::Normal;
+                    }
+                    i + 2
+                } else {
+                    i + 1
+                }
+            }
+            ScanState::LineComment => {
+                if c == '\n' {
+                    *state = ScanState::Normal;
+                }
+                i + 1
+            }
+        }
+    }
+}
+
+fn is_identifier_char(c: char) -> bool {
+    c.is_alphanumeric() || c == '_' || c == '$'
+}
+
+/// The `$tag$` starting at `i`, if there is one. Positional parameters
+/// such as `$1` are not tags.
+fn dollar_tag(chars: &[char], i: usize) -> Option<String> {
+    let mut end = i + 1;
+    while end < chars.len() && chars[end] != '$' {
+        let c = chars[end];
+        let valid = if end == i + 1 {
+            c.is_alphabetic() || c == '_'
+        } else {
+            c.is_alphanumeric() || c == '_'
+        };
+        if !valid {
+            return None;
+        }
+        end += 1;
+    }
+    if end < chars.len() {
+        Some(chars[i..=end].iter().collect())
+    } else {
+        None
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn push_lines(lines: &[&str]) -> (Vec<String>, StatementBuffer) {
+        let mut buffer = StatementBuffer::new();
+        let statements = lines.iter().flat_map(|l| buffer.push_line(l)).collect();
+        (statements, buffer)
+    }
+
+    #[test]
+    fn several_statements_on_one_line() {
+        let (statements, buffer) = push_lines(&["select 1; select 2 ;select 3"]);
+        assert_eq!(statements, ["select 1;", "select 2 ;"]);
+        assert!(!buffer.is_empty());
+        let (statements, buffer) = push_lines(&["select 1;;  select 2; -- done"]);
+        assert_eq!(statements, ["select 1;", "select 2;"]);
+        assert!(buffer.is_empty());
+    }
+
+    #[test]
+    fn statement_over_several_lines() {
+        let (statements, buffer) = push_lines(&["select", "  1", "from t;"]);
+        assert_eq!(statements, ["select\n  1\nfrom t;"]);
+        assert!(buffer.is_empty());
+    }
+
+    #[test]
+    fn semicolons_in_strings_and_identifiers() {
+        let (statements, _) = push_lines(&["select 'a;b', \"c;d\", 'it''s;';"]);
+        assert_eq!(statements, ["select 'a;b', \"c;d\", 'it''s;';"]);
+        let (statements, buffer) = push_lines(&["select 'a;", "b';"]);
+        assert_eq!(statements, ["select 'a;\nb';"]);
+        assert!(buffer.is_empty());
+    }
+
+    #[test]
+    fn escape_strings() {
+        let (statements, _) = push_lines(&[r"select E'it\'s;', e'\\';"]);
+        assert_eq!(statements, [r"select E'it\'s;', e'\\';"]);
+        // Only a standalone E starts an escape string.
+        let (statements, _) = push_lines(&[r"select type'\'; select 1;"]);
+        assert_eq!(statements, [r"select type'\';", "select 1;"]);
+    }
+
+    #[test]
+    fn dollar_quoted_bodies() {
+        let (statements, buffer) = push_lines(&[
+            "create function f() returns int as $body$",
+            "  select 1; select $$;$$;",
+            "$body$ language sql;",
+        ]);
+        assert_eq!(statements.len(), 1);
+        assert!(statements[0].ends_with("$body$ language sql;"));
+        assert!(buffer.is_empty());
+        let (statements, _) = push_lines(&["select $$a;b$$, $x$;$x$;"]);
+        assert_eq!(statements, ["select $$a;b$$, $x$;$x$;"]);
+    }
+
+    #[test]
+    fn positional_parameters_are_not_tags() {
+        let (statements, _) = push_lines(&["select $1; select $2;"]);
+        assert_eq!(statements, ["select $1;", "select $2;"]);
+        let (statements, _) = push_lines(&["select a$b; select 1;"]);
+        assert_eq!(statements, ["select a$b;", "select 1;"]);
+    }
+
+    #[test]
+    fn nested_block_comments() {
+        let (statements, buffer) = push_lines(&["select /* a /* b; */ c; */ 1;"]);
+        assert_eq!(statements, ["select /* a /* b; */ c; */ 1;"]);
+        assert!(buffer.is_empty());
+        let (statements, buffer) = push_lines(&["/* open", "/* nested */ still; */ select 1;"]);
+        assert_eq!(statements, ["/* open\n/* nested */ still; */ select 1;"]);
+        assert!(buffer.is_empty());
+    }
+
+    #[test]
+    fn line_comments() {
+        let (statements, buffer) = push_lines(&["select 1 -- not the end;", ";"]);
+        assert_eq!(statements, ["select 1 -- not the end;\n;"]);
+        assert!(buffer.is_empty());
+    }
+
+    #[test]
+    fn comments_and_empty_statements_are_dropped() {
+        let (statements, buffer) = push_lines(&["-- just a comment", "/* and */ ;", "  "]);
+        assert!(statements.is_empty());
+        assert!(buffer.is_empty());
+    }
+
+    #[test]
+    fn unterminated_input_stays_buffered() {
+        let (statements, mut buffer) = push_lines(&["select 'open"]);
+        assert!(statements.is_empty());
+        assert!(!buffer.is_empty());
+        assert_eq!(buffer.push_line("';"), ["select 'open\n';"]);
+        let (_, buffer) = push_lines(&["/* still open"]);
+        assert!(!buffer.is_empty());
+    }
+}
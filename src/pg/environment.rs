@@ -0,0 +1,337 @@
+use std::{
+    collections::HashMap,
+    env, fs,
+    io::{Error, ErrorKind},
+    path::{Path, PathBuf},
+};
+
+use super::{drop_ignored_keywords, PgTermError};
+
+/// The libpq environment variables and the keywords they stand in for.
+const VARIABLES: &[(&str, &str)] = &[
+    ("PGHOST", "host"),
+    ("PGPORT", "port"),
+    ("PGUSER", "user"),
+    ("PGPASSWORD", "password"),
+    ("PGDATABASE", "dbname"),
+    ("PGSSLMODE", "sslmode"),
+    ("PGSSLROOTCERT", "sslrootcert"),
+    ("PGSSLCERT", "sslcert"),
+    ("PGSSLKEY", "sslkey"),
+    ("PGAPPNAME", "application_name"),
+    ("PGCONNECT_TIMEOUT", "connect_timeout"),
+    ("PGOPTIONS", "options"),
+    ("PGTARGETSESSIONATTRS", "target_session_attrs"),
+    ("PGSERVICE", "service"),
+];
+
+/// Where pg_service.conf is looked for when `PGSYSCONFDIR` is not set:
+/// the default of libpq built from source. libpq from a distribution
+/// looks in the directory it was built with instead, such as
+/// `/etc/postgresql-common` on Debian, so set `PGSYSCONFDIR` to use a
+/// service file there.
+const DEFAULT_SYSCONFDIR: &str = "/usr/local/pgsql/etc";
+const DEFAULT_PORT: &str = "5432";
+
+/// Connection keywords set through `PG*` environment variables.
+pub fn environment_params() -> HashMap<String, String> {
+    VARIABLES
+        .iter()
+        .filter_map(|(variable, keyword)| {
+            env::var(variable)
+                .ok()
+                .filter(|v| !v.is_empty())
+                .map(|v| (keyword.to_string(), v))
+        })
+        .collect()
+}
+
+/// The keywords of `service`, from `~/.pg_service.conf` (or
+/// `PGSERVICEFILE`) if it is defined there, otherwise from
+/// `pg_service.conf` in `PGSYSCONFDIR`. Libpq keywords that pgterm does
+/// not use are left out.
+pub fn service_params(service: &str) -> Result<HashMap<String, String>, PgTermError> {
+    let user_file = env::var_os("PGSERVICEFILE")
+        .map(PathBuf::from)
+        .or_else(|| home_file(".pg_service.conf"));
+    let system_file = env::var_os("PGSYSCONFDIR")
+        .map(PathBuf::from)
+        .unwrap_or_else(|| PathBuf::from(DEFAULT_SYSCONFDIR))
+        .join("pg_service.conf");
+    for path in user_file.iter().chain([&system_file]) {
+        if let Some(params) = read_service(path, service)? {
+            return drop_ignored_keywords(params);
+        }
+    }
+    Err(PgTermError::Parse(format!(
+        "Definition of service {:?} not found",
+        service
+    )))
+}
+
+/// Reads the `[service]` section of an INI-style service file. A missing
+/// file is the same as one without the section.
+fn read_service(
+    path: &Path,
+    service: &str,
+) -> Result<Option<HashMap<String, String>>, PgTermError> {
+    let contents = match fs::read_to_string(path) {
+        Ok(contents) => contents,
+        Err(er) if er.kind() == ErrorKind::NotFound => return Ok(None),
+        Err(er) => return Err(er.into()),
+    };
+    parse_service(&contents, service)
+        .map_err(|line| PgTermError::Parse(format!("Syntax error in {:?} at line {}", path, line)))
+}
+
+/// The keywords of the `[service]` section of a service file's
+/// `contents`. Only that section is read, so a mistake elsewhere does not
+/// matter; one inside it fails with its line number.
+fn parse_service(contents: &str, service: &str) -> Result<Option<HashMap<String, String>>, usize> {
+    let mut found = None;
+    let mut in_service = false;
+    for (i, line) in contents.lines().enumerate() {
+        let line = line.trim();
+        if line.is_empty() || line.starts_with('#') {
+            continue;
+        }
+        if let Some(name) = line.strip_prefix('[').and_then(|l| l.strip_suffix(']')) {
+            if found.is_some() {
+                break;
+            }
+            in_service = name == service;
+            if in_service {
+                found = Some(HashMap::new());
+            }
+            continue;
+        }
+        if let (true, Some(params)) = (in_service, found.as_mut()) {
+            let (key, value) = line.split_once('=').ok_or(i + 1)?;
+            params.insert(key.trim().to_string(), value.trim().to_string());
+        }
+    }
+    Ok(found)
+}
+
+/// Looks up a password in `~/.pgpass` (or `PGPASSFILE`). Each line is
+/// `host:port:database:user:password`, where `*` matches anything and
+/// `\` escapes a `:` or `\`. The file is ignored, with an error, when
+/// others can read it.
+pub fn pgpass_password(
+    host: &str,
+    port: Option<u16>,
+    database: &str,
+    user: &str,
+) -> Result<Option<String>, PgTermError> {
+    let path = match env::var_os("PGPASSFILE")
+        .map(PathBuf::from)
+        .or_else(|| home_file(".pgpass"))
+    {
+        Some(path) => path,
+        None => return Ok(None),
+    };
+    let contents = match fs::read_to_string(&path) {
+        Ok(contents) => contents,
+        Err(er) if er.kind() == ErrorKind::NotFound => return Ok(None),
+        Err(er) => return Err(er.into()),
+    };
+    check_private(&path)?;
+    Ok(match_pgpass(&contents, host, port, database, user))
+}
+
+/// The password of the first line of a password file that matches.
+fn match_pgpass(
+    contents: &str,
+    host: &str,
+    port: Option<u16>,
+    database: &str,
+    user: &str,
+) -> Option<String> {
+    // Unix sockets are matched as localhost.
+    let host = if host.is_empty() || host.starts_with('/') {
+        "localhost"
+    } else {
+        host
+    };
+    let port = port.map(|p| p.to_string());
+    let port = port.as_deref().unwrap_or(DEFAULT_PORT);
+    // The database defaults to the user's name.
+    let database = if database.is_empty() { user } else { database };
+    let wanted = [host, port, database, user];
+
+    for line in contents.lines() {
+        if line.starts_with('#') {
+            continue;
+        }
+        let fields = split_pgpass_line(line);
+        if fields.len() < 5 {
+            continue;
+        }
+        let matches = wanted
+            .iter()
+            .zip(&fields)
+            .all(|(want, field)| field == "*" || field == want);
+        if matches {
+            return Some(fields[4..].join(":"));
+        }
+    }
+    None
+}
+
+fn split_pgpass_line(line: &str) -> Vec<String> {
+    let mut fields = Vec::new();
+    let mut field = String::new();
+    let mut chars = line.chars();
+    while let Some(c) = chars.next() {
+        match c {
+            '\\' => field.extend(chars.next()),
+            ':' => fields.push(std::mem::take(&mut field)),
+            c => field.push(c),
+        }
+    }
+    fields.push(field);
+    fields
+}
+
+#[cfg(unix)]
+fn check_private(path: &Path) -> Result<(), PgTermError> {
+    use std::os::unix::fs::PermissionsExt;
+
+    if fs::metadata(path)?.permissions().mode() & 0o077 != 0 {
+        return Err(PgTermError::Io(Error::new(
+            ErrorKind::PermissionDenied,
+            format!(
+                "password file {:?} has group or world access; permissions should be u=rw (0600) or less",
+                path
+            ),
+        )));
+    }
+    Ok(())
+}
+
+#[cfg(not(unix))]
+fn check_private(_: &Path) -> Result<(), PgTermError> {
+    Ok(())
+}
+
+fn home_file(name: &str) -> Option<PathBuf> {
+    env::var_os("HOME").map(|h| PathBuf::from(h).join(name))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const PGPASS: &str = "\
+# comment:*:*:*:nope
+db.example.com:5432:sales:alice:exact
+db.example.com:*:*:alice:any port or database
+*:5433:*:*:other port
+localhost:5432:*:bob:local
+weird\\:host:5432:*:carol:pass\\:with\\\\escapes
+short:5432:x
+*:*:*:*:fall:back
+";
+
+    fn lookup(host: &str, port: Option<u16>, database: &str, user: &str) -> Option<String> {
+        match_pgpass(PGPASS, host, port, database, user)
+    }
+
+    #[test]
+    fn first_matching_line_wins() {
+        assert_eq!(
+            lookup("db.example.com", Some(5432), "sales", "alice").as_deref(),
+            Some("exact")
+        );
+        assert_eq!(
+            lookup("db.example.com", Some(6000), "hr", "alice").as_deref(),
+            Some("any port or database")
+        );
+        assert_eq!(
+            lookup("elsewhere", Some(5433), "x", "dave").as_deref(),
+            Some("other port")
+        );
+    }
+
+    #[test]
+    fn defaults_fill_in_the_lookup() {
+        // No port is 5432, and sockets are localhost.
+        assert_eq!(
+            lookup("localhost", None, "x", "bob").as_deref(),
+            Some("local")
+        );
+        assert_eq!(
+            lookup("/run/postgresql", None, "x", "bob").as_deref(),
+            Some("local")
+        );
+        assert_eq!(lookup("", None, "x", "bob").as_deref(), Some("local"));
+        // No database is the user's name.
+        assert_eq!(
+            match_pgpass("h:5432:erin:erin:mine", "h", None, "", "erin").as_deref(),
+            Some("mine")
+        );
+    }
+
+    #[test]
+    fn escapes_and_colons_in_passwords() {
+        assert_eq!(
+            lookup("weird:host", None, "x", "carol").as_deref(),
+            Some("pass:with\\escapes")
+        );
+        // An unescaped colon in the password is kept.
+        assert_eq!(
+            lookup("any", Some(1), "x", "y").as_deref(),
+            Some("fall:back")
+        );
+    }
+
+    #[test]
+    fn comments_and_short_lines_never_match() {
+        assert_eq!(
+            match_pgpass(
+                "# comment:*:*:*:nope\nshort:5432:x",
+                "short",
+                None,
+                "x",
+                "y"
+            ),
+            None
+        );
+        assert_eq!(match_pgpass("", "h", None, "d", "u"), None);
+    }
+
+    const SERVICES: &str = "\
+# shared with other clients
+[reporting]
+host = reports.example.com
+hostaddr = 10.0.0.5
+gssencmode = disable
+target_session_attrs = read-write
+
+[broken]
+this line has no equals sign
+
+[typo]
+hots = db
+";
+
+    #[test]
+    fn service_reads_only_its_own_section() {
+        let params = parse_service(SERVICES, "reporting").unwrap().unwrap();
+        assert_eq!(params["host"], "reports.example.com");
+        assert_eq!(params.len(), 4);
+        assert_eq!(parse_service(SERVICES, "broken"), Err(9));
+        assert_eq!(parse_service(SERVICES, "missing"), Ok(None));
+    }
+
+    #[test]
+    fn service_skips_keywords_pgterm_does_not_use() {
+        let params = parse_service(SERVICES, "reporting").unwrap().unwrap();
+        let params = drop_ignored_keywords(params).unwrap();
+        let mut keys = params.keys().map(|k| k.as_str()).collect::<Vec<&str>>();
+        keys.sort();
+        assert_eq!(keys, ["host", "target_session_attrs"]);
+        let typo = parse_service(SERVICES, "typo").unwrap().unwrap();
+        assert!(drop_ignored_keywords(typo).is_err());
+    }
+}
@@ -0,0 +1,104 @@
+use std::fmt::Display;
+
+use postgres::error::{ErrorPosition, SqlState};
+
+#[derive(Debug)]
+pub struct SqlError {
+    pub code: String,
+    pub message: String,
+    pub detail: Option<String>,
+    pub hint: Option<String>,
+    /// 1-based character offset into `query`.
+    pub position: Option<u32>,
+    pub query: Option<String>,
+}
+
+#[derive(Debug)]
+pub enum PgTermError {
+    /// The server could not be reached, or the connection was lost.
+    Connection(String),
+    /// The server rejected the credentials.
+    Auth(String),
+    /// The server reported an error while running a statement.
+    Sql(Box<SqlError>),
+    Io(std::io::Error),
+    /// Input from the user or a file could not be understood.
+    Parse(String),
+}
+
+impl PgTermError {
+    pub fn not_connected() -> PgTermError {
+        PgTermError::Connection("Not connected.".to_string())
+    }
+
+    /// Converts a driver error, keeping `query` so the failing position
+    /// can be shown.
+    pub fn from_query(er: postgres::Error, query: &str) -> PgTermError {
+        let mut error = PgTermError::from(er);
+        if let PgTermError::Sql(sql) = &mut error {
+            if sql.query.is_none() {
+                sql.query = Some(query.to_string());
+            }
+        }
+        error
+    }
+}
+
+impl From<postgres::Error> for PgTermError {
+    fn from(er: postgres::Error) -> Self {
+        let db = match er.as_db_error() {
+            Some(db) => db,
+            // The server asked for a password and none was given.
+            None if std::error::Error::source(&er)
+                .is_some_and(|e| e.to_string() == "password missing") =>
+            {
+                return PgTermError::Auth("a password is required".to_string())
+            }
+            None => return PgTermError::Connection(er.to_string()),
+        };
+        let code = db.code();
+        // Only a wrong password is worth asking for another; a missing
+        // role or pg_hba.conf entry is not.
+        if *code == SqlState::INVALID_PASSWORD {
+            return PgTermError::Auth(db.message().to_string());
+        }
+        if *code == SqlState::INVALID_AUTHORIZATION_SPECIFICATION {
+            return PgTermError::Connection(db.message().to_string());
+        }
+        let (position, query) = match db.position() {
+            Some(ErrorPosition::Original(position)) => (Some(*position), None),
+            Some(ErrorPosition::Internal { position, query }) => {
+                (Some(*position), Some(query.clone()))
+            }
+            None => (None, None),
+        };
+        PgTermError::Sql(Box::new(SqlError {
+            code: code.code().to_string(),
+            message: db.message().to_string(),
+            detail: db.detail().map(|a| a.to_string()),
+            hint: db.hint().map(|a| a.to_string()),
+            position,
+            query,
+        }))
+    }
+}
+
+impl From<std::io::Error> for PgTermError {
+    fn from(er: std::io::Error) -> Self {
+        PgTermError::Io(er)
+    }
+}
+
+impl Display for PgTermError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            PgTermError::Connection(message) => write!(f, "Connection error: {}", message),
+            PgTermError::Auth(message) => write!(f, "Authentication failed: {}", message),
+            PgTermError::Sql(sql) => write!(f, "ERROR {}: {}", sql.code, sql.message),
+            PgTermError::Io(er) => write!(f, "IO error: {}", er),
+            PgTermError::Parse(message) => write!(f, "Parse error: {}", message),
+        }
+    }
+}
+
+impl std::error::Error for PgTermError {}
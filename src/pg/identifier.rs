@@ -0,0 +1,175 @@
+use std::fmt::Display;
+
+use super::PgTermError;
+
+/// Quotes `name` so it can be used as an identifier in a statement,
+/// whatever its case and whether or not it is a reserved word.
+pub fn quote_identifier(name: &str) -> String {
+    format!("\"{}\"", name.replace('"', "\"\""))
+}
+
+/// `name` as it would have to be typed: bare when Postgres would read it
+/// back unchanged, quoted otherwise.
+pub fn display_identifier(name: &str) -> String {
+    let plain = name.starts_with(|c: char| c.is_ascii_lowercase() || c == '_')
+        && name
+            .chars()
+            .all(|c| c.is_ascii_lowercase() || c.is_ascii_digit() || c == '_' || c == '$');
+    if plain {
+        name.to_string()
+    } else {
+        quote_identifier(name)
+    }
+}
+
+/// Splits a dotted name such as `schema.table` into its parts. Unquoted
+/// parts are folded to lower case as Postgres does; quoted parts are kept
+/// as written and may contain dots, spaces and doubled quotes.
+pub fn parse_identifiers(input: &str) -> Result<Vec<String>, PgTermError> {
+    let invalid = || PgTermError::Parse(format!("{:?} is not a valid name", input));
+    let mut parts = Vec::new();
+    let mut chars = input.trim().chars().peekable();
+    loop {
+        let mut part = String::new();
+        if chars.next_if_eq(&'"').is_some() {
+            loop {
+                match chars.next() {
+                    Some('"') if chars.next_if_eq(&'"').is_some() => part.push('"'),
+                    Some('"') => break,
+                    Some(c) => part.push(c),
+                    None => return Err(invalid()),
+                }
+            }
+        } else {
+            while let Some(c) = chars.next_if(|c| *c != '.' && *c != '"') {
+                if c.is_whitespace() {
+                    return Err(invalid());
+                }
+                part.extend(c.to_lowercase());
+            }
+        }
+        if part.is_empty() {
+            return Err(invalid());
+        }
+        parts.push(part);
+        match chars.next() {
+            Some('.') => continue,
+            Some(_) => return Err(invalid()),
+            None => return Ok(parts),
+        }
+    }
+}
+
+/// A table, optionally qualified by its schema. Without a schema the
+/// table is found through the search path.
+#[derive(Clone, PartialEq)]
+pub struct PostgresTableName {
+    pub schema: Option<String>,
+    pub name: String,
+}
+
+impl PostgresTableName {
+    /// Reads `table` or `schema.table` as typed by the user.
+    pub fn parse(input: &str) -> Result<PostgresTableName, PgTermError> {
+        let mut parts = parse_identifiers(input)?;
+        let name = parts.pop().unwrap_or_default();
+        match parts.len() {
+            0 => Ok(PostgresTableName { schema: None, name }),
+            1 => Ok(PostgresTableName {
+                schema: parts.pop(),
+                name,
+            }),
+            _ => Err(PgTermError::Parse(format!(
+                "{:?} is not a valid table name",
+                input
+            ))),
+        }
+    }
+
+    /// The name quoted for use in a statement.
+    pub fn quoted(&self) -> String {
+        match &self.schema {
+            Some(schema) => format!(
+                "{}.{}",
+                quote_identifier(schema),
+                quote_identifier(&self.name)
+            ),
+            None => quote_identifier(&self.name),
+        }
+    }
+}
+
+impl Display for PostgresTableName {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match &self.schema {
+            Some(schema) => write!(
+                f,
+                "{}.{}",
+                display_identifier(schema),
+                display_identifier(&self.name)
+            ),
+            None => write!(f, "{}", display_identifier(&self.name)),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn unquoted_names_fold_to_lower_case() {
+        assert_eq!(parse_identifiers("Users").unwrap(), ["users"]);
+        assert_eq!(
+            parse_identifiers(" Sales.Orders ").unwrap(),
+            ["sales", "orders"]
+        );
+        assert_eq!(parse_identifiers("a.b.c").unwrap(), ["a", "b", "c"]);
+    }
+
+    #[test]
+    fn quoted_names_are_kept_as_written() {
+        assert_eq!(parse_identifiers("\"Users\"").unwrap(), ["Users"]);
+        assert_eq!(
+            parse_identifiers("\"my schema\".\"a.b\"").unwrap(),
+            ["my schema", "a.b"]
+        );
+        assert_eq!(
+            parse_identifiers("\"say \"\"hi\"\"\"").unwrap(),
+            ["say \"hi\""]
+        );
+        assert_eq!(
+            parse_identifiers("Sales.\"Orders\"").unwrap(),
+            ["sales", "Orders"]
+        );
+    }
+
+    #[test]
+    fn invalid_names_are_rejected() {
+        for input in [
+            "", ".", "a.", ".a", "a..b", "a b", "\"open", "\"\"", "\"a\"b",
+        ] {
+            assert!(parse_identifiers(input).is_err(), "{:?}", input);
+        }
+    }
+
+    #[test]
+    fn table_names() {
+        let table = PostgresTableName::parse("Sales.\"Big Orders\"").unwrap();
+        assert_eq!(table.schema.as_deref(), Some("sales"));
+        assert_eq!(table.name, "Big Orders");
+        assert_eq!(table.quoted(), "\"sales\".\"Big Orders\"");
+        assert_eq!(table.to_string(), "sales.\"Big Orders\"");
+        assert!(PostgresTableName::parse("a.b.c").is_err());
+    }
+
+    #[test]
+    fn identifiers_are_quoted_only_when_needed() {
+        assert_eq!(display_identifier("users"), "users");
+        assert_eq!(display_identifier("_a1$"), "_a1$");
+        assert_eq!(display_identifier("Users"), "\"Users\"");
+        assert_eq!(display_identifier("1a"), "\"1a\"");
+        assert_eq!(display_identifier("a b"), "\"a b\"");
+        assert_eq!(quote_identifier("a\"b"), "\"a\"\"b\"");
+    }
+}
@@ -1,3 +1,16 @@
+pub mod conninfo;
+pub mod environment;
+pub mod error;
+pub mod identifier;
+#[allow(clippy::module_inception)]
 pub mod pg;
+pub mod rows;
+pub mod tls;
 
-pub use pg::*;
\ No newline at end of file
+pub use conninfo::*;
+pub use environment::*;
+pub use error::*;
+pub use identifier::*;
+pub use pg::*;
+pub use rows::*;
+pub use tls::*;
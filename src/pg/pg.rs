@@ -1,8 +1,16 @@
-use std::collections::HashMap;
+use std::{collections::HashMap, fmt::Display, iter::Peekable, str::Chars, time::Duration};
 
 use postgres::{
-    types::{private::BytesMut, FromSql, IsNull, ToSql, Type},
-    Client, NoTls,
+    config::TargetSessionAttrs, error::SqlState, types::ToSql, Client, Column, Config, NoTls, Row,
+    SimpleQueryMessage, Statement,
+};
+use zeroize::{Zeroize, Zeroizing};
+
+use super::{
+    check_keywords, environment_params, is_conninfo, parse_conninfo, parse_identifiers,
+    pgpass_password, quote_identifier, service_params, tls_connector, PgTermError,
+    PostgresErrorRow, PostgresRawValue, PostgresRow, PostgresRowMatcher, PostgresSslMode,
+    PostgresTableName, PostgresTextParam,
 };
 
 pub struct PostgresConnection {
@@ -17,24 +25,65 @@ impl PostgresConnection {
         }
     }
 
-    pub fn connect(&mut self) -> bool {
-        let connection_string = format!(
-            "host = {} user = {} {}{} dbname = {}",
-            self.credentials.host,
-            self.credentials.username,
-            if self.credentials.pass_required {
-                "password = "
-            } else {
-                ""
-            },
-            self.credentials.password,
-            self.credentials.database
-        );
-        self.client = match Client::connect(&connection_string, NoTls) {
-            Ok(cn) => Some(cn),
-            Err(_) => None,
+    /// Opens the connection. The password is wiped from memory once it
+    /// has been used, whether or not it was accepted.
+    pub fn connect(&mut self) -> Result<(), PgTermError> {
+        let client = self.open();
+        self.credentials.password.zeroize();
+        self.client = Some(client?);
+        Ok(())
+    }
+
+    /// What the connection was opened with. The password has been wiped.
+    pub fn credentials(&self) -> &PostgresCredentials {
+        &self.credentials
+    }
+
+    /// Replaces the password, for trying again after it was rejected.
+    pub fn set_password(&mut self, password: String) {
+        self.credentials.password = password;
+    }
+
+    fn open(&self) -> Result<Client, PgTermError> {
+        let credentials = &self.credentials;
+        let mut config = Config::new();
+        for host in credentials.host.split(',') {
+            config.host(host.trim());
+        }
+        config
+            .user(&credentials.username)
+            .application_name(
+                credentials
+                    .application_name
+                    .as_deref()
+                    .unwrap_or(PostgresCredentials::DEFAULT_APPLICATION_NAME),
+            )
+            .ssl_mode(credentials.ssl_mode.driver_mode());
+        if !credentials.database.is_empty() {
+            config.dbname(&credentials.database);
+        }
+        if !credentials.password.is_empty() {
+            config.password(&credentials.password);
+        }
+        for port in &credentials.ports {
+            config.port(*port);
+        }
+        // As in libpq, a timeout of zero waits forever.
+        if let Some(timeout) = credentials.connect_timeout.filter(|t| *t > 0) {
+            config.connect_timeout(Duration::from_secs(timeout));
+        }
+        if let Some(options) = &credentials.options {
+            config.options(options);
+        }
+        if credentials.target_session_attrs.as_deref() == Some("read-write") {
+            config.target_session_attrs(TargetSessionAttrs::ReadWrite);
+        }
+
+        let client = match credentials.ssl_mode {
+            PostgresSslMode::Disable => config.connect(NoTls)?,
+            _ => config.connect(tls_connector(credentials)?)?,
         };
-        return self.client.is_some();
+        Ok(client)
     }
 
     pub fn close(self) {
@@ -43,98 +92,787 @@ impl PostgresConnection {
         }
     }
 
-    pub fn get_table(&mut self, table: &String) -> Option<PostgresResult> {
-        if let Some(client) = &mut self.client {
-            let mut column_names: Vec<(String, Type)> = Vec::new();
-            let mut values: Vec<Vec<Box<dyn PostgresRow>>> = Vec::new();
-            let query = client.query(&format!("select * from {}", table), &[]);
-            match query {
-                Ok(rows) => {
-                    for row in rows {
-                        if column_names.len() == 0 {
-                            column_names = row
-                                .columns()
-                                .iter()
-                                .map(|a| (a.name().to_string(), a.type_().clone()))
-                                .collect();
+    fn client(&mut self) -> Result<&mut Client, PgTermError> {
+        self.client.as_mut().ok_or_else(PgTermError::not_connected)
+    }
+
+    /// Starts reading the rows selected by `query`. Rows are fetched from
+    /// a cursor in batches, so only one batch is held in memory at a time.
+    pub fn stream_table(
+        &mut self,
+        query: &PostgresTableQuery,
+    ) -> Result<PostgresRowStream<'_>, PgTermError> {
+        let (sql, params) = query.to_sql();
+        PostgresRowStream::open(self.client()?, &sql, &params)
+    }
+
+    /// How many rows `query` would read, ignoring its limit and offset.
+    pub fn count_rows(&mut self, query: &PostgresTableQuery) -> Result<i64, PgTermError> {
+        let (sql, params) = query.to_count_sql();
+        let params = params
+            .iter()
+            .map(|p| p as &(dyn ToSql + Sync))
+            .collect::<Vec<&(dyn ToSql + Sync)>>();
+        let row = self
+            .client()?
+            .query_one(&sql, &params)
+            .map_err(|er| PgTermError::from_query(er, &sql))?;
+        Ok(row.get(0))
+    }
+
+    /// Runs a single statement typed by the user. Statements that return
+    /// rows produce a `PostgresResult`, anything else its command tag.
+    pub fn run_query(&mut self, query: &str) -> Result<PostgresQueryResult, PgTermError> {
+        let client = self.client()?;
+        let statement = client
+            .prepare(query)
+            .map_err(|er| PgTermError::from_query(er, query))?;
+        if statement.columns().is_empty() {
+            let count = client
+                .execute(&statement, &[])
+                .map_err(|er| PgTermError::from_query(er, query))?;
+            Ok(PostgresQueryResult::Command(
+                PostgresConnection::command_tag(query, count),
+            ))
+        } else if PostgresConnection::has_decoders(statement.columns()) {
+            let rows = client
+                .query(&statement, &[])
+                .map_err(|er| PgTermError::from_query(er, query))?;
+            Ok(PostgresQueryResult::Rows(PostgresConnection::decode_rows(
+                statement.columns(),
+                rows,
+            )))
+        } else {
+            // The driver asks for every column in binary, except over the
+            // simple protocol, where everything is text.
+            let messages = client
+                .simple_query(query)
+                .map_err(|er| PgTermError::from_query(er, query))?;
+            Ok(PostgresQueryResult::Rows(
+                PostgresConnection::decode_text_rows(statement.columns(), &messages),
+            ))
+        }
+    }
+
+    fn has_decoders(columns: &[Column]) -> bool {
+        columns
+            .iter()
+            .all(|c| PostgresRowMatcher::has_decoder(c.type_()))
+    }
+
+    fn decode_text_rows(columns: &[Column], messages: &[SimpleQueryMessage]) -> PostgresResult {
+        PostgresResult {
+            columns: columns.iter().map(|a| a.name().to_string()).collect(),
+            rows: messages
+                .iter()
+                .filter_map(|message| match message {
+                    SimpleQueryMessage::Row(row) => Some(
+                        columns
+                            .iter()
+                            .enumerate()
+                            .map(|(i, c)| PostgresRowMatcher::from_text(c.type_(), row.get(i)))
+                            .collect(),
+                    ),
+                    _ => None,
+                })
+                .collect(),
+        }
+    }
+
+    fn decode_rows(columns: &[Column], rows: Vec<Row>) -> PostgresResult {
+        PostgresResult {
+            columns: columns.iter().map(|a| a.name().to_string()).collect(),
+            rows: rows
+                .iter()
+                .map(|row| PostgresConnection::decode_row(columns, row))
+                .collect(),
+        }
+    }
+
+    fn decode_row(columns: &[Column], row: &Row) -> Vec<Box<dyn PostgresRow>> {
+        columns
+            .iter()
+            .enumerate()
+            .map(
+                |(i, c)| match row.try_get::<usize, Option<PostgresRawValue>>(i) {
+                    Ok(raw) => PostgresRowMatcher::from_sql(
+                        c.type_(),
+                        raw.as_ref().map(|r| r.raw.as_slice()),
+                    ),
+                    Err(er) => {
+                        Box::new(PostgresErrorRow::create(c.type_(), &er)) as Box<dyn PostgresRow>
+                    }
+                },
+            )
+            .collect()
+    }
+
+    /// Rebuilds the tag the server would report (`UPDATE 3`, `CREATE
+    /// TABLE`), since the driver only exposes the affected row count.
+    fn command_tag(query: &str, count: u64) -> String {
+        let tokens = PostgresConnection::statement_tokens(query);
+        let verb = PostgresConnection::verb_of(&tokens);
+        // Only the statement itself counts, not what is in parentheses.
+        let words = tokens
+            .iter()
+            .filter(|(depth, word)| *depth == 0 && word != ";")
+            .map(|(_, word)| word.as_str())
+            .collect::<Vec<&str>>();
+        match verb.as_str() {
+            "INSERT" => format!("INSERT 0 {}", count),
+            "UPDATE" | "DELETE" | "MERGE" | "COPY" | "FETCH" | "MOVE" | "SELECT" => {
+                format!("{} {}", verb, count)
+            }
+            "TRUNCATE" => "TRUNCATE TABLE".to_string(),
+            "REFRESH" => "REFRESH MATERIALIZED VIEW".to_string(),
+            "CREATE" | "DROP" | "ALTER" => {
+                // Modifiers are not part of the tag: `CREATE OR REPLACE
+                // VIEW` reports `CREATE VIEW`.
+                let mut object = words[1..].iter().filter(|a| {
+                    !matches!(
+                        **a,
+                        "OR" | "REPLACE"
+                            | "UNIQUE"
+                            | "TEMP"
+                            | "TEMPORARY"
+                            | "UNLOGGED"
+                            | "GLOBAL"
+                            | "LOCAL"
+                    )
+                });
+                let object = match object.next() {
+                    Some(&first) if first == "MATERIALIZED" || first == "FOREIGN" => {
+                        format!("{} {}", first, object.next().copied().unwrap_or_default())
+                    }
+                    Some(first) => first.to_string(),
+                    None => return verb,
+                };
+                // `CREATE TABLE ... AS` reports the rows it stored, unless
+                // it was asked for none.
+                let is_query = verb == "CREATE"
+                    && (object == "TABLE" || object == "MATERIALIZED VIEW")
+                    && words.contains(&"AS");
+                if is_query && words.ends_with(&["WITH", "NO", "DATA"]) {
+                    if object == "TABLE" {
+                        "CREATE TABLE AS".to_string()
+                    } else {
+                        format!("CREATE {}", object)
+                    }
+                } else if is_query {
+                    format!("SELECT {}", count)
+                } else {
+                    format!("{} {}", verb, object)
+                }
+            }
+            _ => verb,
+        }
+    }
+
+    /// The command a statement runs, in upper case: its first keyword,
+    /// after any comments, or for `WITH` the statement after the queries
+    /// it names.
+    pub fn statement_verb(query: &str) -> String {
+        PostgresConnection::verb_of(&PostgresConnection::statement_tokens(query))
+    }
+
+    fn verb_of(tokens: &[(usize, String)]) -> String {
+        let verb = tokens.first().map(|(_, a)| a.clone()).unwrap_or_default();
+        if verb != "WITH" {
+            return verb;
+        }
+        // `WITH a AS (...), b AS (...) UPDATE ...`: the statement follows
+        // the parenthesis that closes the last query.
+        tokens
+            .windows(2)
+            .find(|pair| {
+                pair[0] == (0, ")".to_string())
+                    && pair[1].0 == 0
+                    && ["SELECT", "INSERT", "UPDATE", "DELETE", "MERGE"]
+                        .contains(&pair[1].1.as_str())
+            })
+            .map(|pair| pair[1].1.clone())
+            .unwrap_or_else(|| "SELECT".to_string())
+    }
+
+    /// Splits a statement into upper-cased words and punctuation, each
+    /// with the depth of parentheses it is at. Comments are dropped and
+    /// quoted strings, identifiers and dollar-quoted bodies become a
+    /// single `'`, `"` or `$` token.
+    fn statement_tokens(query: &str) -> Vec<(usize, String)> {
+        let mut tokens: Vec<(usize, String)> = Vec::new();
+        let mut depth = 0;
+        let mut chars = query.chars().peekable();
+        while let Some(c) = chars.next() {
+            match c {
+                '-' if chars.peek() == Some(&'-') => {
+                    for c in chars.by_ref() {
+                        if c == '\n' {
+                            break;
+                        }
+                    }
+                }
+                '/' if chars.peek() == Some(&'*') => {
+                    chars.next();
+                    // Block comments nest.
+                    let mut nesting = 1;
+                    let mut previous = ' ';
+                    while nesting > 0 {
+                        let Some(c) = chars.next() else { break };
+                        match (previous, c) {
+                            ('/', '*') => {
+                                nesting += 1;
+                                previous = ' ';
+                            }
+                            ('*', '/') => {
+                                nesting -= 1;
+                                previous = ' ';
+                            }
+                            _ => previous = c,
                         }
-                        let mut data: Vec<Box<dyn PostgresRow>> = Vec::new();
-                        for c in &column_names {
-                            if c.1 == Type::BOOL {
-                                let matched: Box<dyn PostgresRow> =
-                                    match row.try_get::<&str, bool>(c.0.as_str()) {
-                                        Ok(val) => Box::new(PostgresBoolRow { value: val }),
-                                        Err(_) => Box::new(PostgresNullRow {}),
-                                    };
-                                data.push(matched)
-                            } else if c.1 == Type::INT2 || c.1 == Type::INT4 || c.1 == Type::INT8 {
-                                let matched: Box<dyn PostgresRow> =
-                                    match row.try_get::<&str, i32>(c.0.as_str()) {
-                                        Ok(val) => Box::new(PostgresI32Row { value: val }),
-                                        Err(_) => Box::new(PostgresNullRow {}),
-                                    };
-                                data.push(matched)
-                            } else if c.1 == Type::TEXT {
-                                let matched: Box<dyn PostgresRow> =
-                                    match row.try_get::<&str, String>(c.0.as_str()) {
-                                        Ok(val) => Box::new(PostgresStringRow { value: val }),
-                                        Err(_) => Box::new(PostgresNullRow {}),
-                                    };
-                                data.push(matched)
+                    }
+                }
+                '\'' | '"' => {
+                    PostgresConnection::skip_quoted(&mut chars, c, false);
+                    tokens.push((depth, c.to_string()));
+                }
+                '$' => {
+                    let mut tag = String::new();
+                    while let Some(&next) = chars.peek() {
+                        if next.is_alphanumeric() || next == '_' {
+                            tag.push(next);
+                            chars.next();
+                        } else {
+                            break;
+                        }
+                    }
+                    // `$1` is a parameter, `$tag$` starts a body that ends
+                    // at the same `$tag$`.
+                    if chars.peek() == Some(&'$') && !tag.starts_with(|c: char| c.is_ascii_digit())
+                    {
+                        chars.next();
+                        let delimiter = format!("${}$", tag);
+                        let mut body = String::new();
+                        for c in chars.by_ref() {
+                            body.push(c);
+                            if body.ends_with(&delimiter) {
+                                break;
                             }
                         }
-                        values.push(data);
                     }
+                    tokens.push((depth, "$".to_string()));
+                }
+                '(' => {
+                    tokens.push((depth, "(".to_string()));
+                    depth += 1;
+                }
+                ')' => {
+                    depth = depth.saturating_sub(1);
+                    tokens.push((depth, ")".to_string()));
+                }
+                c if c.is_alphanumeric() || c == '_' => {
+                    let mut word = c.to_uppercase().to_string();
+                    while let Some(&next) = chars.peek() {
+                        if next.is_alphanumeric() || next == '_' || next == '$' {
+                            word.extend(next.to_uppercase());
+                            chars.next();
+                        } else {
+                            break;
+                        }
+                    }
+                    if word == "E" && chars.peek() == Some(&'\'') {
+                        // Backslashes escape in E'' strings.
+                        chars.next();
+                        PostgresConnection::skip_quoted(&mut chars, '\'', true);
+                        word = "'".to_string();
+                    }
+                    tokens.push((depth, word));
+                }
+                c if c.is_whitespace() => {}
+                c => tokens.push((depth, c.to_string())),
+            }
+        }
+        tokens
+    }
+
+    /// Moves past a string or quoted identifier whose opening `quote` has
+    /// been read.
+    fn skip_quoted(chars: &mut Peekable<Chars>, quote: char, escapes: bool) {
+        while let Some(c) = chars.next() {
+            if escapes && c == '\\' {
+                chars.next();
+            } else if c == quote {
+                // A doubled quote stands for itself.
+                if chars.peek() != Some(&quote) {
+                    break;
                 }
-                Err(er) => println!("{:?}", er),
+                chars.next();
             }
-            Some(PostgresResult {
-                columns: column_names.iter().map(|a| a.0.to_string()).collect(),
-                rows: values,
+        }
+    }
+
+    /// The columns of `table`, in order. Empty if there is no such table.
+    pub fn describe_table(
+        &mut self,
+        table: &PostgresTableName,
+    ) -> Result<Vec<PostgresColumn>, PgTermError> {
+        let client = self.client()?;
+        let query = "select a.attname, pg_catalog.format_type(a.atttypid, a.atttypmod), \
+                     not a.attnotnull \
+                     from pg_catalog.pg_attribute a \
+                     where a.attrelid = pg_catalog.to_regclass($1) \
+                     and a.attnum > 0 and not a.attisdropped \
+                     order by a.attnum";
+        let rows = client
+            .query(query, &[&table.quoted()])
+            .map_err(|er| PgTermError::from_query(er, query))?;
+        Ok(rows
+            .iter()
+            .map(|row| PostgresColumn {
+                name: row.get(0),
+                data_type: row.get(1),
+                is_nullable: row.get(2),
             })
+            .collect())
+    }
+
+    /// The columns of the primary key of `table`, in key order. Empty if
+    /// it has none.
+    pub fn primary_key(&mut self, table: &PostgresTableName) -> Result<Vec<String>, PgTermError> {
+        let client = self.client()?;
+        let query = "select a.attname::text \
+                     from pg_catalog.pg_index i \
+                     join pg_catalog.pg_attribute a \
+                     on a.attrelid = i.indrelid and a.attnum = any(i.indkey) \
+                     where i.indrelid = pg_catalog.to_regclass($1) and i.indisprimary \
+                     order by pg_catalog.array_position(i.indkey::int2[], a.attnum)";
+        let rows = client
+            .query(query, &[&table.quoted()])
+            .map_err(|er| PgTermError::from_query(er, query))?;
+        Ok(rows.iter().map(|row| row.get(0)).collect())
+    }
+
+    /// Everything about `table` that `describe` shows: columns with their
+    /// defaults and comments, constraints, indexes and triggers.
+    pub fn describe_table_details(
+        &mut self,
+        table: &PostgresTableName,
+    ) -> Result<PostgresTableDescription, PgTermError> {
+        let client = self.client()?;
+        // Casting fails with the usual error if there is no such table.
+        let query = "select $1::text::regclass::oid, \
+                     pg_catalog.obj_description($1::text::regclass, 'pg_class')";
+        let row = client
+            .query_one(query, &[&table.quoted()])
+            .map_err(|er| PgTermError::from_query(er, query))?;
+        let oid: u32 = row.get(0);
+        let comment: Option<String> = row.get(1);
+
+        // Identity columns came with PostgreSQL 10, generated ones with 12.
+        let row = client.query_one(
+            "select pg_catalog.current_setting('server_version_num')::int",
+            &[],
+        )?;
+        let version: i32 = row.get(0);
+        let identity = if version >= 100000 {
+            "a.attidentity::text"
         } else {
-            None
-        }
-    }
-
-    pub fn describe_table(&mut self, table: &String) -> Option<Vec<PostgresColumn>> {
-        if let Some(client) = &mut self.client {
-            let mut values: Vec<PostgresColumn> = Vec::new();
-            let query = client.query(&format!("select column_name, data_type, is_nullable from information_schema.columns where table_name='{}';", table), &[]);
-            match query {
-                Ok(rows) => {
-                    for row in rows {
-                        let t: &str = row.get("is_nullable");
-                        values.push(PostgresColumn {
-                            name: row.get("column_name"),
-                            data_type: row.get("data_type"),
-                            is_nullable: if t == "NO" { false } else { true },
-                        });
-                    }
+            "''"
+        };
+        let generated = if version >= 120000 {
+            "a.attgenerated::text"
+        } else {
+            "''"
+        };
+        let query = format!(
+            "select a.attname, pg_catalog.format_type(a.atttypid, a.atttypmod), \
+             not a.attnotnull, pg_catalog.pg_get_expr(d.adbin, d.adrelid), \
+             {}, {}, pg_catalog.col_description(a.attrelid, a.attnum) \
+             from pg_catalog.pg_attribute a \
+             left join pg_catalog.pg_attrdef d \
+             on d.adrelid = a.attrelid and d.adnum = a.attnum \
+             where a.attrelid = $1 and a.attnum > 0 and not a.attisdropped \
+             order by a.attnum",
+            identity, generated
+        );
+        let columns = client
+            .query(&query, &[&oid])
+            .map_err(|er| PgTermError::from_query(er, &query))?
+            .iter()
+            .map(|row| {
+                let expression: Option<String> = row.get(3);
+                let identity: String = row.get(4);
+                let generated: String = row.get(5);
+                // A generated column keeps its expression as its default.
+                let (default, generated) = match (generated.as_str(), expression) {
+                    ("s", Some(expression)) => (None, Some(PostgresGenerated::Stored(expression))),
+                    ("v", Some(expression)) => (None, Some(PostgresGenerated::Virtual(expression))),
+                    (_, expression) => (expression, None),
+                };
+                PostgresColumnDescription {
+                    column: PostgresColumn {
+                        name: row.get(0),
+                        data_type: row.get(1),
+                        is_nullable: row.get(2),
+                    },
+                    default,
+                    identity: match identity.as_str() {
+                        "a" => Some(PostgresIdentity::Always),
+                        "d" => Some(PostgresIdentity::ByDefault),
+                        _ => None,
+                    },
+                    generated,
+                    comment: row.get(6),
+                }
+            })
+            .collect();
+
+        // Not-null constraints are already shown with the columns.
+        let query = "select conname, contype::text, pg_catalog.pg_get_constraintdef(oid), \
+                     case when confrelid <> 0 then confrelid::regclass::text end \
+                     from pg_catalog.pg_constraint \
+                     where conrelid = $1 and contype <> 'n' \
+                     order by contype, conname";
+        let constraints = client
+            .query(query, &[&oid])
+            .map_err(|er| PgTermError::from_query(er, query))?
+            .iter()
+            .map(|row| {
+                let kind: String = row.get(1);
+                PostgresConstraint {
+                    name: row.get(0),
+                    kind: PostgresConstraintKind::from_contype(&kind),
+                    definition: row.get(2),
+                    references: row.get(3),
+                }
+            })
+            .collect();
+
+        let query = "select c.relname, pg_catalog.pg_get_indexdef(i.indexrelid), \
+                     i.indisprimary, i.indisunique \
+                     from pg_catalog.pg_index i \
+                     join pg_catalog.pg_class c on c.oid = i.indexrelid \
+                     where i.indrelid = $1 \
+                     order by i.indisprimary desc, c.relname";
+        let indexes = client
+            .query(query, &[&oid])
+            .map_err(|er| PgTermError::from_query(er, query))?
+            .iter()
+            .map(|row| PostgresIndex {
+                name: row.get(0),
+                definition: row.get(1),
+                is_primary: row.get(2),
+                is_unique: row.get(3),
+            })
+            .collect();
+
+        let query = "select tgname, pg_catalog.pg_get_triggerdef(oid) \
+                     from pg_catalog.pg_trigger \
+                     where tgrelid = $1 and not tgisinternal \
+                     order by tgname";
+        let triggers = client
+            .query(query, &[&oid])
+            .map_err(|er| PgTermError::from_query(er, query))?
+            .iter()
+            .map(|row| PostgresTrigger {
+                name: row.get(0),
+                definition: row.get(1),
+            })
+            .collect();
+
+        Ok(PostgresTableDescription {
+            columns,
+            constraints,
+            indexes,
+            triggers,
+            comment,
+        })
+    }
+
+    /// Every table outside the system schemas. Tables that can be found
+    /// through the search path are returned without their schema.
+    pub fn list_tables(&mut self) -> Result<Vec<PostgresTableName>, PgTermError> {
+        Ok(self
+            .list_relations(None)?
+            .iter()
+            .map(|r| r.table_name())
+            .collect())
+    }
+
+    /// Tables, views, materialized views and foreign tables, in `schema`
+    /// or in every schema outside the system ones.
+    pub fn list_relations(
+        &mut self,
+        schema: Option<&str>,
+    ) -> Result<Vec<PostgresRelation>, PgTermError> {
+        let client = self.client()?;
+        let query = "select n.nspname, c.relname, c.relkind::text, \
+                     pg_catalog.pg_table_is_visible(c.oid) \
+                     from pg_catalog.pg_class c \
+                     join pg_catalog.pg_namespace n on n.oid = c.relnamespace \
+                     where c.relkind in ('r', 'p', 'v', 'm', 'f') \
+                     and ($1::text is null and n.nspname <> 'information_schema' \
+                     and n.nspname not like 'pg\\_%' or n.nspname = $1) \
+                     order by 1, 2";
+        let rows = client
+            .query(query, &[&schema])
+            .map_err(|er| PgTermError::from_query(er, query))?;
+        Ok(rows
+            .iter()
+            .map(|row| {
+                let kind: String = row.get(2);
+                PostgresRelation {
+                    schema: row.get(0),
+                    name: row.get(1),
+                    kind: PostgresRelationKind::from_relkind(&kind),
+                    visible: row.get(3),
                 }
-                Err(er) => println!("{:?}", er),
+            })
+            .collect())
+    }
+
+    /// Every table outside the system schemas with its column names, in
+    /// order, read in one query. Tables are named as by `list_tables`.
+    pub fn list_table_columns(
+        &mut self,
+    ) -> Result<Vec<(PostgresTableName, Vec<String>)>, PgTermError> {
+        let client = self.client()?;
+        let query = "select n.nspname, c.relname, pg_catalog.pg_table_is_visible(c.oid), \
+                     array(select a.attname::text from pg_catalog.pg_attribute a \
+                     where a.attrelid = c.oid and a.attnum > 0 and not a.attisdropped \
+                     order by a.attnum) \
+                     from pg_catalog.pg_class c \
+                     join pg_catalog.pg_namespace n on n.oid = c.relnamespace \
+                     where c.relkind in ('r', 'p', 'v', 'm', 'f') \
+                     and n.nspname <> 'information_schema' and n.nspname not like 'pg\\_%' \
+                     order by 1, 2";
+        let rows = client
+            .query(query, &[])
+            .map_err(|er| PgTermError::from_query(er, query))?;
+        Ok(rows
+            .iter()
+            .map(|row| {
+                let visible: bool = row.get(2);
+                let table = PostgresTableName {
+                    schema: if visible { None } else { Some(row.get(0)) },
+                    name: row.get(1),
+                };
+                (table, row.get(3))
+            })
+            .collect())
+    }
+
+    /// Which of `tables` names the same relation as `table`, however each
+    /// is qualified. None if there is no such relation among them.
+    pub fn find_table(
+        &mut self,
+        table: &PostgresTableName,
+        tables: &[PostgresTableName],
+    ) -> Result<Option<PostgresTableName>, PgTermError> {
+        let client = self.client()?;
+        let names = tables.iter().map(|t| t.quoted()).collect::<Vec<String>>();
+        let query = "select i::int from unnest($2::text[]) with ordinality as u(name, i) \
+                     where pg_catalog.to_regclass(name) = pg_catalog.to_regclass($1) \
+                     limit 1";
+        let row = client
+            .query_opt(query, &[&table.quoted(), &names])
+            .map_err(|er| PgTermError::from_query(er, query))?;
+        Ok(row.map(|row| tables[row.get::<usize, i32>(0) as usize - 1].clone()))
+    }
+
+    /// Every schema outside the system ones, with its owner.
+    pub fn list_schemas(&mut self) -> Result<Vec<(String, String)>, PgTermError> {
+        let client = self.client()?;
+        let query = "select n.nspname, pg_catalog.pg_get_userbyid(n.nspowner) \
+                     from pg_catalog.pg_namespace n \
+                     where n.nspname <> 'information_schema' \
+                     and n.nspname not like 'pg\\_%' \
+                     order by 1";
+        let rows = client
+            .query(query, &[])
+            .map_err(|er| PgTermError::from_query(er, query))?;
+        Ok(rows.iter().map(|row| (row.get(0), row.get(1))).collect())
+    }
+
+    pub fn search_path(&mut self) -> Result<String, PgTermError> {
+        let client = self.client()?;
+        let row = client.query_one("select pg_catalog.current_setting('search_path')", &[])?;
+        Ok(row.get(0))
+    }
+
+    /// Sets the schemas searched for unqualified names, for the rest of
+    /// the session. Every schema must exist.
+    pub fn set_search_path(&mut self, schemas: &[String]) -> Result<(), PgTermError> {
+        let client = self.client()?;
+        let quoted = schemas
+            .iter()
+            .map(|a| quote_identifier(a))
+            .collect::<Vec<String>>();
+        // Reading each name as a schema fails for any that do not exist.
+        let query = "select s::regnamespace from unnest($1::text[]) s";
+        client
+            .query(query, &[&quoted])
+            .map_err(|er| PgTermError::from_query(er, query))?;
+        let path = quoted.join(", ");
+        client.execute(
+            "select pg_catalog.set_config('search_path', $1, false)",
+            &[&path],
+        )?;
+        Ok(())
+    }
+
+    /// Makes transactions read-only by default for the rest of the
+    /// session.
+    pub fn set_read_only(&mut self) -> Result<(), PgTermError> {
+        self.client()?
+            .batch_execute("set session characteristics as transaction read only")?;
+        Ok(())
+    }
+
+    /// Creates any missing tables and inserts every row in a single
+    /// transaction, or a savepoint if the user has one open. Nothing is
+    /// kept unless every table succeeds. Returns the number of rows
+    /// inserted per table.
+    pub fn import_tables(
+        &mut self,
+        tables: &[PostgresTable],
+    ) -> Result<Vec<(String, u64)>, PgTermError> {
+        let client = self.client()?;
+        let work = PostgresWork::begin(client)?;
+        match PostgresConnection::insert_tables(client, tables) {
+            Ok(counts) => {
+                work.commit(client)?;
+                Ok(counts)
+            }
+            Err(er) => {
+                _ = work.rollback(client);
+                Err(er)
             }
-            Some(values)
-        } else {
-            None
         }
     }
 
-    pub fn list_tables(&mut self) -> Option<Vec<String>> {
-        if let Some(client) = &mut self.client {
-            let tables = client
-                .query("select table_name from information_schema.tables where table_schema != 'pg_catalog' AND table_schema != 'information_schema'", &[])
-                .ok()?;
-            Some(
-                tables
+    fn insert_tables(
+        client: &mut Client,
+        tables: &[PostgresTable],
+    ) -> Result<Vec<(String, u64)>, PgTermError> {
+        let mut counts: Vec<(String, u64)> = Vec::new();
+        let null: Option<PostgresRawValue> = None;
+        for table in tables {
+            let name = PostgresTableName::parse(&table.name)?;
+            if let Some(schema) = &name.schema {
+                let create = format!("create schema if not exists {}", quote_identifier(schema));
+                client
+                    .batch_execute(&create)
+                    .map_err(|er| PgTermError::from_query(er, &create))?;
+            }
+            let name = name.quoted();
+            let column_definitions = table
+                .columns
+                .iter()
+                .map(|c| {
+                    format!(
+                        "{} {}{}",
+                        quote_identifier(&c.name),
+                        c.data_type,
+                        if c.is_nullable { "" } else { " not null" }
+                    )
+                })
+                .collect::<Vec<String>>()
+                .join(", ");
+            let create = format!(
+                "create table if not exists {} ({})",
+                name, column_definitions
+            );
+            client
+                .batch_execute(&create)
+                .map_err(|er| PgTermError::from_query(er, &create))?;
+
+            let column_names = table
+                .columns
+                .iter()
+                .map(|c| quote_identifier(&c.name))
+                .collect::<Vec<String>>()
+                .join(", ");
+            let placeholders = table
+                .columns
+                .iter()
+                .enumerate()
+                .map(|(i, c)| {
+                    if PostgresRowMatcher::is_native(&c.data_type) {
+                        format!("${}", i + 1)
+                    } else {
+                        format!("${}::text::{}", i + 1, c.data_type)
+                    }
+                })
+                .collect::<Vec<String>>()
+                .join(", ");
+            let insert = format!(
+                "insert into {} ({}) values ({})",
+                name, column_names, placeholders
+            );
+            let statement = client
+                .prepare(&insert)
+                .map_err(|er| PgTermError::from_query(er, &insert))?;
+
+            let mut inserted = 0;
+            for row in &table.data {
+                let values = row.iter().map(|v| v.value()).collect::<Vec<_>>();
+                let params = values
                     .iter()
-                    .map(|a| a.get("table_name"))
-                    .filter(|a: &String| !a.starts_with(&"pg_"))
-                    .collect(),
-            )
+                    .map(|v| match v {
+                        Some(val) => val.as_ref() as &(dyn ToSql + Sync),
+                        None => &null as &(dyn ToSql + Sync),
+                    })
+                    .collect::<Vec<&(dyn ToSql + Sync)>>();
+                inserted += client.execute(&statement, &params)?;
+            }
+            counts.push((table.name.clone(), inserted));
+        }
+        Ok(counts)
+    }
+}
+
+/// Work that is kept or undone as a whole. It is a transaction of its own
+/// unless the user has opened one at the prompt, in which case it is a
+/// savepoint inside theirs, so that their transaction is never ended.
+struct PostgresWork {
+    nested: bool,
+}
+
+impl PostgresWork {
+    const SAVEPOINT: &'static str = "pgterm_work";
+
+    fn begin(client: &mut Client) -> Result<PostgresWork, PgTermError> {
+        // A savepoint can only be made inside a transaction block, which
+        // tells whether one is open.
+        match client.batch_execute(&format!("savepoint {}", PostgresWork::SAVEPOINT)) {
+            Ok(()) => Ok(PostgresWork { nested: true }),
+            Err(er) if er.code() == Some(&SqlState::NO_ACTIVE_SQL_TRANSACTION) => {
+                client.batch_execute("begin")?;
+                Ok(PostgresWork { nested: false })
+            }
+            Err(er) => Err(er.into()),
+        }
+    }
+
+    fn commit(&self, client: &mut Client) -> Result<(), PgTermError> {
+        if self.nested {
+            client.batch_execute(&format!("release savepoint {}", PostgresWork::SAVEPOINT))?;
+        } else {
+            client.batch_execute("commit")?;
+        }
+        Ok(())
+    }
+
+    fn rollback(&self, client: &mut Client) -> Result<(), PgTermError> {
+        if self.nested {
+            client.batch_execute(&format!(
+                "rollback to savepoint {0}; release savepoint {0}",
+                PostgresWork::SAVEPOINT
+            ))?;
         } else {
-            None
+            client.batch_execute("rollback")?;
         }
+        Ok(())
     }
 }
 
@@ -143,6 +881,12 @@ pub struct PostgresTable {
     pub data: Vec<Vec<Box<dyn PostgresRow>>>,
     pub name: String,
 }
+impl Default for PostgresTable {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
 impl PostgresTable {
     pub fn new() -> PostgresTable {
         PostgresTable {
@@ -158,123 +902,902 @@ pub struct PostgresResult {
     pub rows: Vec<Vec<Box<dyn PostgresRow>>>,
 }
 
-pub struct PostgresColumn {
-    pub name: String,
-    pub data_type: String,
-    pub is_nullable: bool,
+/// Which rows of a table to read, and in what order.
+#[derive(Clone)]
+pub struct PostgresTableQuery {
+    pub table: PostgresTableName,
+    pub limit: Option<u64>,
+    pub offset: u64,
+    pub order_by: Option<String>,
+    pub descending: bool,
+    pub filter: Option<PostgresFilter>,
+    /// Columns that tell rows apart, such as the primary key. They are
+    /// ordered by after `order_by`, so that pages do not overlap.
+    pub key: Vec<String>,
 }
 
-pub trait PostgresRow {
-    fn display(&self) -> String;
-    fn value(&self) -> Option<Box<dyn ToSql>>;
-}
+impl PostgresTableQuery {
+    /// Every row of `table`.
+    pub fn new(table: PostgresTableName) -> PostgresTableQuery {
+        PostgresTableQuery {
+            table,
+            limit: None,
+            offset: 0,
+            order_by: None,
+            descending: false,
+            filter: None,
+            key: Vec::new(),
+        }
+    }
 
-pub struct PostgresRowMatcher {}
-impl PostgresRowMatcher {
-    pub fn match_type(t: &String, data: &String) -> Box<dyn PostgresRow> {
-        if t == &"boolean".to_string() {
-            Box::new(PostgresBoolRow { value: data == "true" })
-        } else if t == &"text".to_string() {
-            Box::new(PostgresStringRow { value: data.clone() })
-        } else if t == &"number".to_string() {
-            Box::new(PostgresI32Row { value: data.parse().unwrap_or(0) })
-        } else {
-            Box::new(PostgresNullRow {})
+    /// The statement and the parameters to bind to it.
+    pub fn to_sql(&self) -> (String, Vec<PostgresTextParam>) {
+        let mut query = format!("select * from {}", self.table.quoted());
+        let params = self.where_clause(&mut query);
+        let mut order = Vec::new();
+        if let Some(column) = &self.order_by {
+            let direction = if self.descending { " desc" } else { "" };
+            order.push(format!("{}{}", quote_identifier(column), direction));
+        }
+        for column in &self.key {
+            if self.order_by.as_ref() != Some(column) {
+                order.push(quote_identifier(column));
+            }
+        }
+        if !order.is_empty() {
+            query += &format!(" order by {}", order.join(", "));
+        }
+        if let Some(limit) = self.limit {
+            query += &format!(" limit {}", limit);
+        }
+        if self.offset > 0 {
+            query += &format!(" offset {}", self.offset);
+        }
+        (query, params)
+    }
+
+    /// Counts the rows matching the filter, ignoring limit and order.
+    pub fn to_count_sql(&self) -> (String, Vec<PostgresTextParam>) {
+        let mut query = format!("select count(*) from {}", self.table.quoted());
+        let params = self.where_clause(&mut query);
+        (query, params)
+    }
+
+    fn where_clause(&self, query: &mut String) -> Vec<PostgresTextParam> {
+        match &self.filter {
+            Some(PostgresFilter::Compare {
+                column,
+                operator,
+                value,
+            }) => {
+                *query += &format!(" where {} {} $1", column, operator);
+                vec![PostgresTextParam {
+                    text: value.clone(),
+                }]
+            }
+            Some(PostgresFilter::Expression(expression)) => {
+                *query += &format!(" where {}", expression);
+                Vec::new()
+            }
+            None => Vec::new(),
         }
     }
 }
 
-pub struct PostgresStringRow {
-    value: String,
+/// A `where` condition typed by the user. A column compared with a single
+/// string literal is sent with the string as a parameter; anything else,
+/// numbers and booleans included, is sent as written.
+#[derive(Clone)]
+pub enum PostgresFilter {
+    Compare {
+        /// Already quoted.
+        column: String,
+        operator: String,
+        value: String,
+    },
+    Expression(String),
 }
-impl PostgresRow for PostgresStringRow {
-    fn display(&self) -> String {
-        return self.value.clone();
+
+impl PostgresFilter {
+    /// Longest first, so `<=` is not read as `<`.
+    const OPERATORS: &[&str] = &[
+        "<=",
+        ">=",
+        "<>",
+        "!=",
+        "=",
+        "<",
+        ">",
+        "not ilike",
+        "not like",
+        "ilike",
+        "like",
+    ];
+
+    pub fn parse(expression: &str) -> PostgresFilter {
+        let expression = expression.trim();
+        PostgresFilter::parse_compare(expression)
+            .unwrap_or_else(|| PostgresFilter::Expression(expression.to_string()))
+    }
+
+    fn parse_compare(expression: &str) -> Option<PostgresFilter> {
+        let end = PostgresFilter::identifier_end(expression);
+        let column = &expression[..end];
+        if column.is_empty() || column.starts_with(|c: char| c.is_ascii_digit()) {
+            return None;
+        }
+        let column = parse_identifiers(column)
+            .ok()?
+            .iter()
+            .map(|a| quote_identifier(a))
+            .collect::<Vec<String>>()
+            .join(".");
+        let rest = expression[end..].trim_start();
+        let lower = rest.to_ascii_lowercase();
+        let operator = PostgresFilter::OPERATORS.iter().find(|o| {
+            // Word operators need a space after them, symbols do not.
+            lower.starts_with(*o)
+                && (!o.ends_with(char::is_alphabetic)
+                    || lower[o.len()..].starts_with(char::is_whitespace))
+        })?;
+        let value = PostgresFilter::literal(rest[operator.len()..].trim())?;
+        Some(PostgresFilter::Compare {
+            column,
+            operator: operator.to_string(),
+            value,
+        })
+    }
+
+    /// Where a leading, possibly quoted and dotted, column name ends.
+    fn identifier_end(expression: &str) -> usize {
+        let mut quoted = false;
+        for (i, c) in expression.char_indices() {
+            if c == '"' {
+                quoted = !quoted;
+            } else if !(quoted || c.is_alphanumeric() || c == '_' || c == '.' || c == '$') {
+                return i;
+            }
+        }
+        expression.len()
     }
-    fn value(&self) -> Option<Box<dyn ToSql>> {
-        return Some(Box::new(self.value.clone()));
+
+    /// The value of a quoted string literal. A parameter takes the type
+    /// of the column, as an untyped string literal does, so the two mean
+    /// the same. Numbers and booleans are typed in SQL and stay in the
+    /// expression: bound, `1.5` would fail against an integer column.
+    fn literal(text: &str) -> Option<String> {
+        let inner = text.strip_prefix('\'')?.strip_suffix('\'')?;
+        // Every quote inside must be doubled.
+        if inner.replace("''", "").contains('\'') {
+            return None;
+        }
+        Some(inner.replace("''", "'"))
     }
 }
-pub struct PostgresI32Row {
-    value: i32,
+
+/// Rows read incrementally from a cursor. The cursor is declared inside
+/// a `PostgresWork`, which is rolled back when the stream is dropped.
+pub struct PostgresRowStream<'a> {
+    pub columns: Vec<String>,
+    client: &'a mut Client,
+    work: PostgresWork,
+    fetch: Statement,
+    /// The cursor's declaration, which errors while fetching point into.
+    declare: String,
 }
-impl PostgresRow for PostgresI32Row {
-    fn display(&self) -> String {
-        return self.value.to_string();
+
+impl<'a> PostgresRowStream<'a> {
+    const CURSOR: &'static str = "pgterm_cursor";
+    const BATCH_SIZE: i32 = 1000;
+
+    fn open(
+        client: &'a mut Client,
+        query: &str,
+        params: &[PostgresTextParam],
+    ) -> Result<PostgresRowStream<'a>, PgTermError> {
+        let work = PostgresWork::begin(client)?;
+        match PostgresRowStream::declare(client, query, params) {
+            Ok((fetch, declare)) => Ok(PostgresRowStream {
+                columns: fetch
+                    .columns()
+                    .iter()
+                    .map(|a| a.name().to_string())
+                    .collect(),
+                client,
+                work,
+                fetch,
+                declare,
+            }),
+            Err(er) => {
+                _ = work.rollback(client);
+                Err(er)
+            }
+        }
+    }
+
+    /// Declares the cursor and returns the statement that fetches from it,
+    /// along with the declaration.
+    fn declare(
+        client: &mut Client,
+        query: &str,
+        params: &[PostgresTextParam],
+    ) -> Result<(Statement, String), PgTermError> {
+        let params = params
+            .iter()
+            .map(|p| p as &(dyn ToSql + Sync))
+            .collect::<Vec<&(dyn ToSql + Sync)>>();
+        let statement = client
+            .prepare(query)
+            .map_err(|er| PgTermError::from_query(er, query))?;
+        let mut query = query.to_string();
+        if !PostgresConnection::has_decoders(statement.columns()) {
+            // Columns that cannot be decoded are read as text instead.
+            let columns = statement
+                .columns()
+                .iter()
+                .map(|c| {
+                    let name = quote_identifier(c.name());
+                    if PostgresRowMatcher::has_decoder(c.type_()) {
+                        name
+                    } else {
+                        format!("{0}::text as {0}", name)
+                    }
+                })
+                .collect::<Vec<String>>()
+                .join(", ");
+            query = format!("select {} from ({}) as pgterm_rows", columns, query);
+        }
+        let declare = format!(
+            "declare {} no scroll cursor for {}",
+            PostgresRowStream::CURSOR,
+            query
+        );
+        client
+            .execute(&declare, &params)
+            .map_err(|er| PgTermError::from_query(er, &declare))?;
+        let fetch = format!(
+            "fetch {} from {}",
+            PostgresRowStream::BATCH_SIZE,
+            PostgresRowStream::CURSOR
+        );
+        let fetch = client
+            .prepare(&fetch)
+            .map_err(|er| PgTermError::from_query(er, &fetch))?;
+        Ok((fetch, declare))
     }
-    fn value(&self) -> Option<Box<dyn ToSql>> {
-        return Some(Box::new(self.value));
+
+    /// The next batch of rows, empty once every row has been read.
+    pub fn next_batch(&mut self) -> Result<Vec<Vec<Box<dyn PostgresRow>>>, PgTermError> {
+        let rows = self
+            .client
+            .query(&self.fetch, &[])
+            .map_err(|er| PgTermError::from_query(er, &self.declare))?;
+        Ok(rows
+            .iter()
+            .map(|row| PostgresConnection::decode_row(self.fetch.columns(), row))
+            .collect())
     }
 }
-pub struct PostgresBoolRow {
-    value: bool,
+
+impl Drop for PostgresRowStream<'_> {
+    fn drop(&mut self) {
+        // Closes the cursor, and leaves any transaction the user opened
+        // as it was.
+        _ = self.work.rollback(self.client);
+    }
 }
-impl PostgresRow for PostgresBoolRow {
-    fn display(&self) -> String {
-        return if self.value {
-            "true".to_string()
-        } else {
-            "false".to_string()
-        };
+
+pub enum PostgresQueryResult {
+    Rows(PostgresResult),
+    Command(String),
+}
+
+pub struct PostgresRelation {
+    pub schema: String,
+    pub name: String,
+    pub kind: PostgresRelationKind,
+    /// Whether the relation can be found through the search path.
+    pub visible: bool,
+}
+
+impl PostgresRelation {
+    /// The name to refer to the relation by, qualified only if needed.
+    pub fn table_name(&self) -> PostgresTableName {
+        PostgresTableName {
+            schema: if self.visible {
+                None
+            } else {
+                Some(self.schema.clone())
+            },
+            name: self.name.clone(),
+        }
     }
-    fn value(&self) -> Option<Box<dyn ToSql>> {
-        return Some(Box::new(self.value));
+}
+
+pub enum PostgresRelationKind {
+    Table,
+    PartitionedTable,
+    View,
+    MaterializedView,
+    ForeignTable,
+}
+
+impl PostgresRelationKind {
+    fn from_relkind(relkind: &str) -> PostgresRelationKind {
+        match relkind {
+            "p" => PostgresRelationKind::PartitionedTable,
+            "v" => PostgresRelationKind::View,
+            "m" => PostgresRelationKind::MaterializedView,
+            "f" => PostgresRelationKind::ForeignTable,
+            _ => PostgresRelationKind::Table,
+        }
     }
 }
-pub struct PostgresNullRow {}
-impl PostgresRow for PostgresNullRow {
-    fn display(&self) -> String {
-        return String::new();
+
+impl Display for PostgresRelationKind {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            PostgresRelationKind::Table => write!(f, "table"),
+            PostgresRelationKind::PartitionedTable => write!(f, "partitioned table"),
+            PostgresRelationKind::View => write!(f, "view"),
+            PostgresRelationKind::MaterializedView => write!(f, "materialized view"),
+            PostgresRelationKind::ForeignTable => write!(f, "foreign table"),
+        }
     }
+}
+
+pub struct PostgresTableDescription {
+    pub columns: Vec<PostgresColumnDescription>,
+    pub constraints: Vec<PostgresConstraint>,
+    pub indexes: Vec<PostgresIndex>,
+    pub triggers: Vec<PostgresTrigger>,
+    pub comment: Option<String>,
+}
+
+pub struct PostgresColumnDescription {
+    pub column: PostgresColumn,
+    /// The default expression, if the column is not generated.
+    pub default: Option<String>,
+    pub identity: Option<PostgresIdentity>,
+    pub generated: Option<PostgresGenerated>,
+    pub comment: Option<String>,
+}
+
+pub enum PostgresIdentity {
+    Always,
+    ByDefault,
+}
+
+/// The expression of a generated column, computed when the row is
+/// written or, since PostgreSQL 18, when it is read.
+pub enum PostgresGenerated {
+    Stored(String),
+    Virtual(String),
+}
+
+pub struct PostgresConstraint {
+    pub name: String,
+    pub kind: PostgresConstraintKind,
+    /// As the server would write it, e.g. `CHECK ((price > 0))`.
+    pub definition: String,
+    /// The table a foreign key refers to.
+    pub references: Option<String>,
+}
+
+#[derive(PartialEq)]
+pub enum PostgresConstraintKind {
+    PrimaryKey,
+    Unique,
+    Check,
+    ForeignKey,
+    Exclusion,
+    Trigger,
+}
 
-    fn value(&self) -> Option<Box<dyn ToSql>> {
-        return None;
+impl PostgresConstraintKind {
+    fn from_contype(contype: &str) -> PostgresConstraintKind {
+        match contype {
+            "p" => PostgresConstraintKind::PrimaryKey,
+            "u" => PostgresConstraintKind::Unique,
+            "f" => PostgresConstraintKind::ForeignKey,
+            "x" => PostgresConstraintKind::Exclusion,
+            "t" => PostgresConstraintKind::Trigger,
+            _ => PostgresConstraintKind::Check,
+        }
     }
 }
 
+pub struct PostgresIndex {
+    pub name: String,
+    /// The `CREATE INDEX` statement that would recreate it.
+    pub definition: String,
+    pub is_primary: bool,
+    pub is_unique: bool,
+}
+
+pub struct PostgresTrigger {
+    pub name: String,
+    /// The `CREATE TRIGGER` statement that would recreate it.
+    pub definition: String,
+}
+
+pub struct PostgresColumn {
+    pub name: String,
+    pub data_type: String,
+    pub is_nullable: bool,
+}
+
 #[derive(Debug)]
 pub struct PostgresCredentials {
+    /// One host, or several separated by commas to be tried in turn. A
+    /// path is the directory of a Unix socket.
     pub host: String,
     pub username: String,
     pub password: String,
     pub database: String,
     pub pass_required: bool,
+    /// One port for every host, or one per host. Empty for the default.
+    pub ports: Vec<u16>,
+    pub application_name: Option<String>,
+    /// Seconds to wait for the server; zero waits forever.
+    pub connect_timeout: Option<u64>,
+    /// Command-line options sent to the server, such as `-c search_path=x`.
+    pub options: Option<String>,
+    /// `any` or `read-write`.
+    pub target_session_attrs: Option<String>,
+    pub ssl_mode: PostgresSslMode,
+    /// PEM file of the CA the server certificate must be signed by.
+    pub ssl_root_cert: Option<String>,
+    /// PEM files of the client certificate and its PKCS#8 key.
+    pub ssl_cert: Option<String>,
+    pub ssl_key: Option<String>,
 }
 
 impl PostgresCredentials {
+    /// Where a connection string given without a flag is kept.
+    pub const CONNINFO_KEY: &str = "conninfo";
     const HOST_KEY: &str = "url";
     const USERNAME_KEY: &str = "user";
     const PASSWORD_KEY: &str = "pass";
     const DATABASE_KEY: &str = "db";
     const NOPASS_KEY: &str = "np";
+    const PORT_KEY: &str = "port";
+    const APPLICATION_NAME_KEY: &str = "application_name";
+    const CONNECT_TIMEOUT_KEY: &str = "connect_timeout";
+    const OPTIONS_KEY: &str = "options";
+    const TARGET_SESSION_ATTRS_KEY: &str = "target_session_attrs";
+    const SSL_MODE_KEY: &str = "sslmode";
+    const SSL_ROOT_CERT_KEY: &str = "sslrootcert";
+    const SSL_CERT_KEY: &str = "sslcert";
+    const SSL_KEY_KEY: &str = "sslkey";
+    const SERVICE_KEY: &str = "service";
+    const DEFAULT_APPLICATION_NAME: &str = "pgterm";
+    const DEFAULT_PORT: u16 = 5432;
+
+    /// Reads the startup flags. A connection string may be given on its
+    /// own or in place of the host; flags given alongside it take
+    /// precedence. `defaults` are libpq keywords, such as those of a
+    /// profile, used where neither gave a value. Anything left unset is
+    /// taken from a `service` and the `PG*` environment variables.
+    pub fn create_from_params(
+        params: &HashMap<String, String>,
+        defaults: &HashMap<String, impl AsRef<str>>,
+    ) -> Result<PostgresCredentials, PgTermError> {
+        // Every copy of the settings is wiped when dropped, as one of
+        // them is the password.
+        let mut merged: HashMap<String, Zeroizing<String>> = HashMap::new();
+        let host_conninfo = params
+            .get(PostgresCredentials::HOST_KEY)
+            .filter(|h| is_conninfo(h));
+        for conninfo in [params.get(PostgresCredentials::CONNINFO_KEY), host_conninfo]
+            .into_iter()
+            .flatten()
+        {
+            for (keyword, value) in parse_conninfo(conninfo)? {
+                merged.insert(
+                    PostgresCredentials::flag_key(&keyword).to_string(),
+                    Zeroizing::new(value),
+                );
+            }
+        }
+        for (key, value) in params {
+            let is_host_conninfo = key == PostgresCredentials::HOST_KEY && is_conninfo(value);
+            if key != PostgresCredentials::CONNINFO_KEY && !is_host_conninfo {
+                merged.insert(key.clone(), Zeroizing::new(value.clone()));
+            }
+        }
+        for (keyword, value) in defaults {
+            merged
+                .entry(PostgresCredentials::flag_key(keyword).to_string())
+                .or_insert_with(|| Zeroizing::new(value.as_ref().to_string()));
+        }
+        // As in libpq, a service fills in what was not given, then the
+        // environment fills in what is still missing.
+        let environment = environment_params();
+        let service = merged
+            .get(PostgresCredentials::SERVICE_KEY)
+            .map(|service| service.to_string())
+            .or_else(|| environment.get(PostgresCredentials::SERVICE_KEY).cloned());
+        if let Some(service) = service {
+            for (keyword, value) in service_params(&service)? {
+                merged
+                    .entry(PostgresCredentials::flag_key(&keyword).to_string())
+                    .or_insert(Zeroizing::new(value));
+            }
+        }
+        for (keyword, value) in environment {
+            merged
+                .entry(PostgresCredentials::flag_key(&keyword).to_string())
+                .or_insert(Zeroizing::new(value));
+        }
 
-    pub fn create_from_params(params: &HashMap<String, String>) -> PostgresCredentials {
-        let host = params
-            .get(&PostgresCredentials::HOST_KEY.to_string())
-            .unwrap_or(&String::new())
-            .to_string();
-        let username = params
-            .get(&PostgresCredentials::USERNAME_KEY.to_string())
-            .unwrap_or(&String::new())
-            .to_string();
-        let password = params
-            .get(&PostgresCredentials::PASSWORD_KEY.to_string())
-            .unwrap_or(&String::new())
-            .to_string();
-        let database = params
-            .get(&PostgresCredentials::DATABASE_KEY.to_string())
-            .unwrap_or(&String::new())
-            .to_string();
-        let nopass = params
-            .get(&PostgresCredentials::NOPASS_KEY.to_string())
-            .unwrap_or(&String::new())
-            .to_string();
-        PostgresCredentials {
+        let get = |key: &str| merged.get(key).map(|value| value.to_string());
+        let host = get(PostgresCredentials::HOST_KEY).unwrap_or_default();
+        // As in libpq, an empty entry in a list of ports is the default.
+        let ports = match get(PostgresCredentials::PORT_KEY) {
+            Some(ports) => ports
+                .split(',')
+                .map(|port| match port.trim() {
+                    "" => Ok(PostgresCredentials::DEFAULT_PORT),
+                    port => port
+                        .parse::<u16>()
+                        .map_err(|_| PgTermError::Parse(format!("{:?} is not a valid port", port))),
+                })
+                .collect::<Result<Vec<u16>, PgTermError>>()?,
+            None => Vec::new(),
+        };
+        let host_count = host.split(',').count();
+        if ports.len() > 1 && ports.len() != host_count {
+            return Err(PgTermError::Parse(format!(
+                "{} ports given for {} hosts",
+                ports.len(),
+                host_count
+            )));
+        }
+        let connect_timeout = match get(PostgresCredentials::CONNECT_TIMEOUT_KEY) {
+            Some(timeout) => Some(timeout.parse::<u64>().map_err(|_| {
+                PgTermError::Parse(format!("{:?} is not a number of seconds", timeout))
+            })?),
+            None => None,
+        };
+        let target_session_attrs = get(PostgresCredentials::TARGET_SESSION_ATTRS_KEY);
+        if let Some(attrs) = &target_session_attrs {
+            if attrs != "any" && attrs != "read-write" {
+                return Err(PgTermError::Parse(format!(
+                    "{:?} is not a target_session_attrs, use any or read-write",
+                    attrs
+                )));
+            }
+        }
+        let ssl_mode = match get(PostgresCredentials::SSL_MODE_KEY) {
+            Some(mode) => PostgresSslMode::parse(&mode).ok_or_else(|| {
+                PgTermError::Parse(format!(
+                    "{:?} is not an sslmode, use disable, prefer, require, verify-ca or verify-full",
+                    mode
+                ))
+            })?,
+            None => PostgresSslMode::Prefer,
+        };
+        Ok(PostgresCredentials {
             host,
-            username,
-            password,
-            database,
-            pass_required: nopass != "y",
+            username: get(PostgresCredentials::USERNAME_KEY).unwrap_or_default(),
+            password: get(PostgresCredentials::PASSWORD_KEY).unwrap_or_default(),
+            database: get(PostgresCredentials::DATABASE_KEY).unwrap_or_default(),
+            pass_required: get(PostgresCredentials::NOPASS_KEY).as_deref() != Some("y"),
+            ports,
+            application_name: get(PostgresCredentials::APPLICATION_NAME_KEY),
+            connect_timeout,
+            options: get(PostgresCredentials::OPTIONS_KEY),
+            target_session_attrs,
+            ssl_mode,
+            ssl_root_cert: get(PostgresCredentials::SSL_ROOT_CERT_KEY),
+            ssl_cert: get(PostgresCredentials::SSL_CERT_KEY),
+            ssl_key: get(PostgresCredentials::SSL_KEY_KEY),
+        })
+    }
+
+    /// Credentials for another connection: the libpq `keywords` given
+    /// replace these settings and everything else is kept.
+    pub fn with_keywords(
+        &self,
+        mut keywords: HashMap<String, String>,
+    ) -> Result<PostgresCredentials, PgTermError> {
+        check_keywords(&keywords)?;
+        // A service replaces the settings it names, like the keywords
+        // given with it.
+        if let Some(service) = keywords.get(PostgresCredentials::SERVICE_KEY).cloned() {
+            for (keyword, value) in service_params(&service)? {
+                keywords.entry(keyword).or_insert(value);
+            }
+        }
+        let mut merged = self.keywords();
+        merged.extend(
+            keywords
+                .into_iter()
+                .map(|(keyword, value)| (keyword, Zeroizing::new(value))),
+        );
+        let mut credentials = PostgresCredentials::create_from_params(&HashMap::new(), &merged)?;
+        credentials.pass_required = self.pass_required || !credentials.password.is_empty();
+        Ok(credentials)
+    }
+
+    /// These settings as libpq keywords, wiped when dropped.
+    pub fn keywords(&self) -> HashMap<String, Zeroizing<String>> {
+        let text = |a: &str| Some(a.to_string()).filter(|a| !a.is_empty());
+        [
+            ("host", text(&self.host)),
+            ("user", text(&self.username)),
+            ("password", text(&self.password)),
+            ("dbname", text(&self.database)),
+            (
+                "port",
+                text(
+                    &self
+                        .ports
+                        .iter()
+                        .map(|a| a.to_string())
+                        .collect::<Vec<String>>()
+                        .join(","),
+                ),
+            ),
+            ("application_name", self.application_name.clone()),
+            (
+                "connect_timeout",
+                self.connect_timeout.map(|a| a.to_string()),
+            ),
+            ("options", self.options.clone()),
+            ("target_session_attrs", self.target_session_attrs.clone()),
+            ("sslmode", Some(self.ssl_mode.to_string())),
+            ("sslrootcert", self.ssl_root_cert.clone()),
+            ("sslcert", self.ssl_cert.clone()),
+            ("sslkey", self.ssl_key.clone()),
+        ]
+        .into_iter()
+        .filter_map(|(keyword, value)| value.map(|v| (keyword.to_string(), Zeroizing::new(v))))
+        .collect()
+    }
+
+    /// Removes and wipes the startup flags that can hold a password once
+    /// credentials have been made from them: the password itself and a
+    /// connection string, given alone or as the host.
+    pub fn wipe_params(params: &mut HashMap<String, String>) {
+        let host_conninfo = params
+            .get(PostgresCredentials::HOST_KEY)
+            .is_some_and(|h| is_conninfo(h));
+        let mut keys = vec![
+            PostgresCredentials::PASSWORD_KEY,
+            PostgresCredentials::CONNINFO_KEY,
+        ];
+        if host_conninfo {
+            keys.push(PostgresCredentials::HOST_KEY);
+        }
+        for key in keys {
+            if let Some(mut value) = params.remove(key) {
+                value.zeroize();
+            }
+        }
+    }
+
+    /// The password for these credentials in the password file, trying
+    /// each host, with its port, in turn.
+    pub fn password_from_pgpass(&self) -> Result<Option<String>, PgTermError> {
+        for (i, host) in self.host.split(',').enumerate() {
+            let port = self.ports.get(i).or(self.ports.first()).copied();
+            let password = pgpass_password(host.trim(), port, &self.database, &self.username)?;
+            if password.is_some() {
+                return Ok(password);
+            }
+        }
+        Ok(None)
+    }
+
+    /// The startup flag for a libpq connection keyword.
+    fn flag_key(keyword: &str) -> &str {
+        match keyword {
+            "host" => PostgresCredentials::HOST_KEY,
+            "password" => PostgresCredentials::PASSWORD_KEY,
+            "dbname" => PostgresCredentials::DATABASE_KEY,
+            _ => keyword,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// The statement `get users where ...` would run, and its parameters.
+    fn filtered(expression: &str) -> (String, Vec<String>) {
+        let mut query = PostgresTableQuery::new(PostgresTableName::parse("users").unwrap());
+        query.filter = Some(PostgresFilter::parse(expression));
+        let (sql, params) = query.to_sql();
+        (sql, params.into_iter().map(|p| p.text).collect())
+    }
+
+    #[test]
+    fn filter_binds_string_literals() {
+        assert_eq!(
+            filtered("name = 'bob'"),
+            (
+                "select * from \"users\" where \"name\" = $1".to_string(),
+                vec!["bob".to_string()]
+            )
+        );
+        assert_eq!(
+            filtered("  Name<>'it''s; drop table users'  "),
+            (
+                "select * from \"users\" where \"name\" <> $1".to_string(),
+                vec!["it's; drop table users".to_string()]
+            )
+        );
+        assert_eq!(filtered("x >= ''").1, [""]);
+    }
+
+    #[test]
+    fn filter_quotes_columns() {
+        assert_eq!(
+            filtered("\"Full Name\" like 'a%'").0,
+            "select * from \"users\" where \"Full Name\" like $1"
+        );
+        assert_eq!(
+            filtered("u.email not ilike '%@x'").0,
+            "select * from \"users\" where \"u\".\"email\" not ilike $1"
+        );
+    }
+
+    #[test]
+    fn filter_keeps_numbers_and_keywords_as_written() {
+        for expression in [
+            "id > 5",
+            "price = 1.5",
+            "a = inf",
+            "a = nan",
+            "flag = true",
+            "a = null",
+            "a = b",
+        ] {
+            assert_eq!(
+                filtered(expression),
+                (
+                    format!("select * from \"users\" where {}", expression),
+                    vec![]
+                ),
+            );
+        }
+    }
+
+    #[test]
+    fn filter_keeps_other_expressions_as_written() {
+        for expression in [
+            "name = 'a' or name = 'b'",
+            "name = 'unterminated",
+            "name = 'a'b'",
+            "lower(name) = 'bob'",
+            "id in (1, 2)",
+            "1 = 'x'",
+        ] {
+            assert_eq!(
+                filtered(expression),
+                (
+                    format!("select * from \"users\" where {}", expression),
+                    vec![]
+                ),
+            );
         }
     }
+
+    #[test]
+    fn filter_word_operators_need_a_space() {
+        // `likes` is a column, not the `like` operator.
+        assert_eq!(
+            filtered("likes = 'x'").0,
+            "select * from \"users\" where \"likes\" = $1"
+        );
+    }
+
+    #[test]
+    fn wipe_params_removes_passwords() {
+        let mut params = HashMap::from([
+            ("pass".to_string(), "secret".to_string()),
+            ("user".to_string(), "bob".to_string()),
+            (
+                "url".to_string(),
+                "postgres://bob:secret@db/app".to_string(),
+            ),
+        ]);
+        PostgresCredentials::wipe_params(&mut params);
+        assert_eq!(
+            params,
+            HashMap::from([("user".to_string(), "bob".to_string())])
+        );
+
+        let mut params = HashMap::from([
+            ("conninfo".to_string(), "password=secret".to_string()),
+            ("url".to_string(), "db.example.com".to_string()),
+        ]);
+        PostgresCredentials::wipe_params(&mut params);
+        assert_eq!(
+            params,
+            HashMap::from([("url".to_string(), "db.example.com".to_string())])
+        );
+    }
+
+    #[test]
+    fn command_tag_skips_leading_comments() {
+        let tag = |query| PostgresConnection::command_tag(query, 2);
+        assert_eq!(tag("-- note\nupdate t set a = 1;"), "UPDATE 2");
+        assert_eq!(tag("/* a /* nested */ note */ delete from t"), "DELETE 2");
+        assert_eq!(
+            tag("-- one\n-- two\n  insert into t values (1)"),
+            "INSERT 0 2"
+        );
+    }
+
+    #[test]
+    fn command_tag_uses_the_statement_after_with() {
+        let tag = |query| PostgresConnection::command_tag(query, 3);
+        assert_eq!(
+            tag("with x as (select 1), y(a) as (select 2) update t set a = 1"),
+            "UPDATE 3"
+        );
+        assert_eq!(
+            tag("with recursive \"update\" as (select ')' from t) delete from t"),
+            "DELETE 3"
+        );
+        assert_eq!(
+            tag("WITH x AS MATERIALIZED (SELECT 1) INSERT INTO t SELECT * FROM x"),
+            "INSERT 0 3"
+        );
+        assert_eq!(tag("with x as (select $$)$$) select 1 into y"), "SELECT 3");
+    }
+
+    #[test]
+    fn command_tag_reports_rows_for_create_as() {
+        let tag = |query| PostgresConnection::command_tag(query, 4);
+        assert_eq!(tag("create table t as select 1"), "SELECT 4");
+        assert_eq!(
+            tag("create temp table t as select 1 with no data"),
+            "CREATE TABLE AS"
+        );
+        assert_eq!(tag("create materialized view v as select 1"), "SELECT 4");
+        assert_eq!(
+            tag("create materialized view v as select 1 with no data;"),
+            "CREATE MATERIALIZED VIEW"
+        );
+        assert_eq!(
+            tag("create table t (a int generated always as (1) stored)"),
+            "CREATE TABLE"
+        );
+        assert_eq!(tag("create or replace view v as select 1"), "CREATE VIEW");
+    }
+
+    #[test]
+    fn command_tag_names_other_commands() {
+        let tag = |query| PostgresConnection::command_tag(query, 0);
+        assert_eq!(tag("drop foreign table f"), "DROP FOREIGN TABLE");
+        assert_eq!(tag("truncate t"), "TRUNCATE TABLE");
+        assert_eq!(
+            tag("refresh materialized view v"),
+            "REFRESH MATERIALIZED VIEW"
+        );
+        assert_eq!(tag("set e.x = E'it\\'s (';"), "SET");
+        assert_eq!(tag("begin"), "BEGIN");
+    }
+
+    #[test]
+    fn table_query_orders_by_the_key_after_the_chosen_column() {
+        let mut query = PostgresTableQuery::new(PostgresTableName::parse("users").unwrap());
+        query.limit = Some(10);
+        query.key = vec!["id".to_string(), "Region".to_string()];
+        assert_eq!(
+            query.to_sql().0,
+            "select * from \"users\" order by \"id\", \"Region\" limit 10"
+        );
+        query.order_by = Some("id".to_string());
+        query.descending = true;
+        assert_eq!(
+            query.to_sql().0,
+            "select * from \"users\" order by \"id\" desc, \"Region\" limit 10"
+        );
+    }
 }
@@ -0,0 +1,180 @@
+use std::{fmt::Display, fs};
+
+use native_tls::{Certificate, Identity, TlsConnector};
+use postgres::config::SslMode;
+use postgres_native_tls::MakeTlsConnector;
+
+use super::{PgTermError, PostgresCredentials};
+
+/// How hard to insist on an encrypted connection, as in libpq.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum PostgresSslMode {
+    Disable,
+    /// Encrypt when the server supports it, without checking its
+    /// certificate.
+    Prefer,
+    /// Always encrypt. The certificate is only checked when a root
+    /// certificate is given.
+    Require,
+    /// Always encrypt and check the certificate is signed by a trusted CA.
+    VerifyCa,
+    /// As `VerifyCa`, and check the certificate is for the host connected to.
+    VerifyFull,
+}
+
+impl PostgresSslMode {
+    pub fn parse(mode: &str) -> Option<PostgresSslMode> {
+        match mode {
+            "disable" => Some(PostgresSslMode::Disable),
+            "prefer" => Some(PostgresSslMode::Prefer),
+            "require" => Some(PostgresSslMode::Require),
+            "verify-ca" => Some(PostgresSslMode::VerifyCa),
+            "verify-full" => Some(PostgresSslMode::VerifyFull),
+            _ => None,
+        }
+    }
+
+    /// The mode the driver understands. It only decides whether TLS is
+    /// attempted; verification is left to the connector.
+    pub fn driver_mode(&self) -> SslMode {
+        match self {
+            PostgresSslMode::Disable => SslMode::Disable,
+            PostgresSslMode::Prefer => SslMode::Prefer,
+            _ => SslMode::Require,
+        }
+    }
+
+    /// Whether the server certificate's CA and its host name are checked,
+    /// given whether a root certificate was named.
+    pub fn checks(&self, has_root_cert: bool) -> (bool, bool) {
+        match self {
+            PostgresSslMode::Disable | PostgresSslMode::Prefer => (false, false),
+            // libpq checks the CA in require mode when given a root certificate.
+            PostgresSslMode::Require => (has_root_cert, false),
+            PostgresSslMode::VerifyCa => (true, false),
+            PostgresSslMode::VerifyFull => (true, true),
+        }
+    }
+}
+
+impl Display for PostgresSslMode {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            PostgresSslMode::Disable => write!(f, "disable"),
+            PostgresSslMode::Prefer => write!(f, "prefer"),
+            PostgresSslMode::Require => write!(f, "require"),
+            PostgresSslMode::VerifyCa => write!(f, "verify-ca"),
+            PostgresSslMode::VerifyFull => write!(f, "verify-full"),
+        }
+    }
+}
+
+/// Builds the TLS connector for `credentials`. Certificates and keys are
+/// read from PEM files; without a root certificate the system's trusted
+/// CAs are used.
+pub fn tls_connector(credentials: &PostgresCredentials) -> Result<MakeTlsConnector, PgTermError> {
+    let mut builder = TlsConnector::builder();
+    let (check_ca, check_host) = credentials
+        .ssl_mode
+        .checks(credentials.ssl_root_cert.is_some());
+    builder
+        .danger_accept_invalid_certs(!check_ca)
+        .danger_accept_invalid_hostnames(!check_host);
+
+    if let Some(path) = &credentials.ssl_root_cert {
+        let root = Certificate::from_pem(&read_pem(path)?)
+            .map_err(|er| tls_error("root certificate", path, er))?;
+        builder.add_root_certificate(root);
+    }
+    match (&credentials.ssl_cert, &credentials.ssl_key) {
+        (Some(cert), Some(key)) => {
+            let identity = Identity::from_pkcs8(&read_pem(cert)?, &read_pem(key)?)
+                .map_err(|er| tls_error("client certificate", cert, er))?;
+            builder.identity(identity);
+        }
+        (None, None) => {}
+        _ => {
+            return Err(PgTermError::Parse(
+                "A client certificate needs both sslcert and sslkey.".to_string(),
+            ))
+        }
+    }
+
+    let connector = builder
+        .build()
+        .map_err(|er| PgTermError::Connection(format!("Could not set up TLS: {}", er)))?;
+    Ok(MakeTlsConnector::new(connector))
+}
+
+fn read_pem(path: &str) -> Result<Vec<u8>, PgTermError> {
+    fs::read(path)
+        .map_err(|er| PgTermError::Connection(format!("Could not read {:?}: {}", path, er)))
+}
+
+fn tls_error(what: &str, path: &str, er: native_tls::Error) -> PgTermError {
+    PgTermError::Parse(format!("Invalid {} in {:?}: {}", what, path, er))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const MODES: [PostgresSslMode; 5] = [
+        PostgresSslMode::Disable,
+        PostgresSslMode::Prefer,
+        PostgresSslMode::Require,
+        PostgresSslMode::VerifyCa,
+        PostgresSslMode::VerifyFull,
+    ];
+
+    #[test]
+    fn ssl_modes_parse_as_written() {
+        for mode in MODES {
+            assert_eq!(PostgresSslMode::parse(&mode.to_string()), Some(mode));
+        }
+        assert_eq!(PostgresSslMode::parse("verify_full"), None);
+        assert_eq!(PostgresSslMode::parse("Require"), None);
+        assert_eq!(PostgresSslMode::parse(""), None);
+    }
+
+    #[test]
+    fn only_disable_and_prefer_may_skip_tls() {
+        let driver = MODES.map(|mode| mode.driver_mode());
+        assert!(matches!(
+            driver,
+            [
+                SslMode::Disable,
+                SslMode::Prefer,
+                SslMode::Require,
+                SslMode::Require,
+                SslMode::Require
+            ]
+        ));
+    }
+
+    #[test]
+    fn verify_modes_check_what_they_name() {
+        // (CA, host name) without and with a root certificate.
+        let checks = |mode: PostgresSslMode| (mode.checks(false), mode.checks(true));
+        assert_eq!(
+            checks(PostgresSslMode::Disable),
+            ((false, false), (false, false))
+        );
+        assert_eq!(
+            checks(PostgresSslMode::Prefer),
+            ((false, false), (false, false))
+        );
+        assert_eq!(
+            checks(PostgresSslMode::Require),
+            ((false, false), (true, false))
+        );
+        assert_eq!(
+            checks(PostgresSslMode::VerifyCa),
+            ((true, false), (true, false))
+        );
+        assert_eq!(
+            checks(PostgresSslMode::VerifyFull),
+            ((true, true), (true, true))
+        );
+    }
+}
@@ -0,0 +1,4 @@
+#[allow(clippy::module_inception)]
+pub mod profile;
+
+pub use profile::{PgTermConfig, PgTermProfile};
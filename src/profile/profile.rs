@@ -0,0 +1,196 @@
+use std::{
+    collections::{BTreeMap, HashMap},
+    env,
+    fmt::Display,
+    fs,
+    io::ErrorKind,
+    path::PathBuf,
+};
+
+use serde::{Deserialize, Serialize};
+
+use crate::pg::{PgTermError, PostgresCredentials};
+
+/// Connection settings saved under a name, so they do not have to be
+/// typed at every start. Passwords are never saved; use `~/.pgpass`.
+#[derive(Clone, Default, Serialize, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct PgTermProfile {
+    pub host: Option<String>,
+    pub port: Option<PgTermPorts>,
+    pub user: Option<String>,
+    pub db: Option<String>,
+    pub sslmode: Option<String>,
+    /// Made the search path once connected, as with `use`.
+    pub schema: Option<String>,
+    /// Starts the session with transactions read-only.
+    #[serde(default, skip_serializing_if = "std::ops::Not::not")]
+    pub read_only: bool,
+    /// Colour of the prompt, such as `red` for production.
+    pub color: Option<String>,
+}
+
+impl PgTermProfile {
+    /// The connection settings of `credentials`, keeping the session
+    /// settings given.
+    pub fn from_credentials(
+        credentials: &PostgresCredentials,
+        schema: Option<String>,
+        read_only: bool,
+        color: Option<String>,
+    ) -> PgTermProfile {
+        let non_empty = |a: &str| Some(a.to_string()).filter(|a| !a.is_empty());
+        PgTermProfile {
+            host: non_empty(&credentials.host),
+            port: match credentials.ports[..] {
+                [] => None,
+                [port] => Some(PgTermPorts::One(port)),
+                ref ports => Some(PgTermPorts::PerHost(ports.to_vec())),
+            },
+            user: non_empty(&credentials.username),
+            db: non_empty(&credentials.database),
+            sslmode: Some(credentials.ssl_mode.to_string()),
+            schema,
+            read_only,
+            color,
+        }
+    }
+
+    /// The connection settings as libpq keywords.
+    pub fn keywords(&self) -> HashMap<String, String> {
+        [
+            ("host", self.host.clone()),
+            ("port", self.port.as_ref().map(|p| p.to_string())),
+            ("user", self.user.clone()),
+            ("dbname", self.db.clone()),
+            ("sslmode", self.sslmode.clone()),
+        ]
+        .into_iter()
+        .filter_map(|(keyword, value)| value.map(|v| (keyword.to_string(), v)))
+        .collect()
+    }
+}
+
+/// `port = 5432`, or `port = [5432, 5433]` for a profile with several
+/// hosts.
+#[derive(Clone, Serialize, Deserialize)]
+#[serde(untagged)]
+pub enum PgTermPorts {
+    One(u16),
+    PerHost(Vec<u16>),
+}
+
+impl Display for PgTermPorts {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            PgTermPorts::One(port) => write!(f, "{}", port),
+            PgTermPorts::PerHost(ports) => write!(
+                f,
+                "{}",
+                ports
+                    .iter()
+                    .map(|a| a.to_string())
+                    .collect::<Vec<String>>()
+                    .join(",")
+            ),
+        }
+    }
+}
+
+/// The contents of `~/.config/pgterm/config.toml`.
+#[derive(Default, Serialize, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct PgTermConfig {
+    #[serde(default)]
+    pub profiles: BTreeMap<String, PgTermProfile>,
+}
+
+impl PgTermConfig {
+    /// Under `$XDG_CONFIG_HOME` when set, otherwise `~/.config`.
+    pub fn path() -> Option<PathBuf> {
+        env::var_os("XDG_CONFIG_HOME")
+            .filter(|a| !a.is_empty())
+            .map(PathBuf::from)
+            .or_else(|| env::var_os("HOME").map(|h| PathBuf::from(h).join(".config")))
+            .map(|dir| dir.join("pgterm").join("config.toml"))
+    }
+
+    /// Reads the config file. A missing file has no profiles.
+    pub fn load() -> Result<PgTermConfig, PgTermError> {
+        let path = match PgTermConfig::path() {
+            Some(path) => path,
+            None => return Ok(PgTermConfig::default()),
+        };
+        let contents = match fs::read_to_string(&path) {
+            Ok(contents) => contents,
+            Err(er) if er.kind() == ErrorKind::NotFound => return Ok(PgTermConfig::default()),
+            Err(er) => return Err(er.into()),
+        };
+        toml::from_str(&contents)
+            .map_err(|er| PgTermError::Parse(format!("{}: {}", path.display(), er.message())))
+    }
+
+    /// Writes the config file, creating its directory if needed, and
+    /// returns where it was written.
+    pub fn save(&self) -> Result<PathBuf, PgTermError> {
+        let path = PgTermConfig::path().ok_or_else(|| {
+            PgTermError::Parse("No home directory to save profiles in.".to_string())
+        })?;
+        let contents =
+            toml::to_string_pretty(self).map_err(|er| PgTermError::Parse(er.to_string()))?;
+        if let Some(dir) = path.parent() {
+            fs::create_dir_all(dir)?;
+        }
+        fs::write(&path, contents)?;
+        Ok(path)
+    }
+
+    pub fn profile(&self, name: &str) -> Result<&PgTermProfile, PgTermError> {
+        self.profiles
+            .get(name)
+            .ok_or_else(|| PgTermError::Parse(format!("No profile named {:?}", name)))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn ports_load_and_save_as_one_or_per_host() {
+        let dir = env::temp_dir().join(format!("pgterm-profiles-{}", std::process::id()));
+        env::set_var("XDG_CONFIG_HOME", &dir);
+        let path = PgTermConfig::path().unwrap();
+        fs::create_dir_all(path.parent().unwrap()).unwrap();
+        fs::write(
+            &path,
+            "[profiles.one]\nhost = \"a\"\nport = 5433\n\n\
+             [profiles.many]\nhost = \"a,b\"\nport = [5432, 5433]\nread_only = true\n",
+        )
+        .unwrap();
+
+        let config = PgTermConfig::load().unwrap();
+        let ports = |config: &PgTermConfig, name| config.profile(name).unwrap().port.clone();
+        assert!(matches!(
+            ports(&config, "one"),
+            Some(PgTermPorts::One(5433))
+        ));
+        assert!(
+            matches!(ports(&config, "many"), Some(PgTermPorts::PerHost(p)) if p == [5432, 5433])
+        );
+
+        config.save().unwrap();
+        assert!(fs::read_to_string(&path).unwrap().contains("port = 5433\n"));
+        let saved = PgTermConfig::load().unwrap();
+        assert!(matches!(ports(&saved, "one"), Some(PgTermPorts::One(5433))));
+        assert!(
+            matches!(ports(&saved, "many"), Some(PgTermPorts::PerHost(p)) if p == [5432, 5433])
+        );
+        assert!(saved.profile("many").unwrap().read_only);
+        assert!(!saved.profile("one").unwrap().read_only);
+
+        fs::write(&path, "[profiles.bad]\nport = \"5432\"\n").unwrap();
+        assert!(PgTermConfig::load().is_err());
+        _ = fs::remove_dir_all(&dir);
+    }
+}